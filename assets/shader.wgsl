@@ -0,0 +1,14 @@
+#include "shader_common.wgsl"
+
+[[block]]
+struct Material {
+    color: vec4<f32>;
+};
+
+[[group(0), binding(0)]]
+var<uniform> material: Material;
+
+[[stage(fragment)]]
+fn fragment() -> [[location(0)]] vec4<f32> {
+    return vec4<f32>(srgb_to_linear(material.color.rgb), material.color.a);
+}
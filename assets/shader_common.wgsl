@@ -0,0 +1,5 @@
+let PI: f32 = 3.14159265358979323846;
+
+fn srgb_to_linear(color: vec3<f32>) -> vec3<f32> {
+    return pow(color, vec3<f32>(2.2));
+}
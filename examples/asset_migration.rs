@@ -0,0 +1,83 @@
+use bevy_app::prelude::*;
+use bevy_app::{AppExit, ScheduleRunnerPlugin};
+use bevy_asset::prelude::*;
+use bevy_asset::schema::{self, AssetSchema};
+use bevy_ecs::prelude::*;
+use bevy_log::prelude::*;
+use bevy_log::LogPlugin;
+use distill_core::{AssetTypeId, AssetUuid};
+
+/// Version 2 added the `roughness`.
+#[derive(Serialize, Deserialize, TypeUuid, Debug)]
+#[uuid = "4d28da28-4263-4991-9e9a-bda72d65ffe4"]
+pub struct Material {
+    pub color: [f32; 3],
+    pub roughness: f32,
+}
+
+/// `Material` as it was at version 1.
+#[derive(Serialize, Deserialize)]
+struct MaterialV1 {
+    color: [f32; 3],
+}
+
+fn migrate_material(
+    old_version: u32,
+    data: &[u8],
+) -> Result<Material, Box<dyn std::error::Error + Send>> {
+    // 1 is the only older version, newer data is rejected before migrating
+    debug_assert_eq!(old_version, 1);
+    let old: MaterialV1 = schema::deserialize(data)?;
+    Ok(Material {
+        color: old.color,
+        roughness: 0.5,
+    })
+}
+
+const MATERIAL: AssetUuid = AssetUuid([2; 16]);
+
+fn main() {
+    // data like it is stored in a packfile built before `roughness` was added
+    let source = MemoryAssetSource::new();
+    let old_data = schema::serialize(
+        1,
+        &MaterialV1 {
+            color: [1.0, 0.0, 0.0],
+        },
+    )
+    .unwrap();
+    source.insert_raw(
+        MATERIAL,
+        Some("material.bmat".into()),
+        AssetTypeId(*Material::TYPE_UUID.as_bytes()),
+        old_data,
+    );
+
+    App::new()
+        .add_plugin(ScheduleRunnerPlugin::default())
+        .add_plugin(LogPlugin)
+        .insert_resource(AssetServerSettings::InMemory(source))
+        .add_plugin(AssetPlugin)
+        .add_asset::<Material>()
+        .add_asset_schema(AssetSchema::<Material>::new(2).with_migration(migrate_material))
+        .add_startup_system(setup)
+        .add_system(system)
+        .run();
+}
+
+struct MaterialHandle(Handle<Material>);
+
+fn setup(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.insert_resource(MaterialHandle(asset_server.load("material.bmat")));
+}
+
+fn system(
+    handle: Res<MaterialHandle>,
+    materials: Res<Assets<Material>>,
+    mut app_exit: EventWriter<AppExit>,
+) {
+    if let Some(material) = materials.get(&handle.0) {
+        info!("{:?}", material);
+        app_exit.send(AppExit);
+    }
+}
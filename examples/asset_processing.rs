@@ -0,0 +1,54 @@
+use bevy_app::prelude::*;
+use bevy_app::{AppExit, ScheduleRunnerPlugin};
+use bevy_asset::importer::text_importer::Text;
+use bevy_asset::importer::TextImporter;
+use bevy_asset::prelude::*;
+use bevy_asset::processor::AssetProcessor;
+use bevy_ecs::prelude::*;
+use bevy_log::prelude::*;
+use bevy_log::LogPlugin;
+
+/// Collapses all whitespace into single spaces.
+struct MinifyText;
+
+impl AssetProcessor<Text> for MinifyText {
+    fn version(&self) -> u32 {
+        1
+    }
+
+    fn process(&self, text: Text) -> Result<Text, Box<dyn std::error::Error + Send>> {
+        Ok(Text(text.split_whitespace().collect::<Vec<_>>().join(" ")))
+    }
+}
+
+fn main() {
+    // run with `raw` as argument to load the text as it was imported
+    let process = std::env::args().nth(1).as_deref() != Some("raw");
+
+    App::new()
+        .add_plugin(ScheduleRunnerPlugin::default())
+        .add_plugin(LogPlugin)
+        .insert_resource(AssetServerSettings::Directory(
+            DirectorySettings::new("assets").with_processing(process),
+        ))
+        .add_plugin(AssetPlugin)
+        .add_asset_loader(&["txt"], TextImporter)
+        .add_asset::<Text>()
+        .add_asset_processor(MinifyText)
+        .add_startup_system(setup)
+        .add_system(system)
+        .run();
+}
+
+struct TextHandle(Handle<Text>);
+
+fn setup(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.insert_resource(TextHandle(asset_server.load("lorem_ipsum.txt")));
+}
+
+fn system(handle: Res<TextHandle>, texts: Res<Assets<Text>>, mut app_exit: EventWriter<AppExit>) {
+    if let Some(text) = texts.get(&handle.0) {
+        info!("{} bytes, {} lines", text.len(), text.lines().count());
+        app_exit.send(AppExit);
+    }
+}
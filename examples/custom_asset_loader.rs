@@ -3,11 +3,10 @@ use bevy_asset::prelude::*;
 
 use bevy_app::prelude::*;
 use bevy_app::ScheduleRunnerPlugin;
-use bevy_asset::util::AssetUuidImporterState;
+use bevy_asset::importer::{ImportContext, Simple, SimpleImporter};
 use bevy_ecs::prelude::*;
 use bevy_log::prelude::*;
 use bevy_log::LogPlugin;
-use distill_importer::{ImportedAsset, Importer, ImporterValue};
 use image::RgbaImage;
 
 #[derive(TypeUuid, Deserialize, Serialize, Clone)]
@@ -29,54 +28,19 @@ impl From<ImageTransfer> for Image {
     }
 }
 
+// The `image-importer` feature provides a complete `ImageImporter`, this shows how to write one.
 #[derive(TypeUuid)]
 #[uuid = "1a8ede27-3963-4ac5-af24-cf95b7cf5640"]
 struct ImageImporter;
-impl Importer for ImageImporter {
-    fn version_static() -> u32
-    where
-        Self: Sized,
-    {
-        1
-    }
-
-    fn version(&self) -> u32 {
-        Self::version_static()
-    }
-
-    type Options = ();
-    type State = AssetUuidImporterState;
-
-    fn import(
-        &self,
-        _: &mut distill_importer::ImportOp,
-        source: &mut dyn std::io::Read,
-        _: &Self::Options,
-        state: &mut Self::State,
-    ) -> distill_importer::Result<ImporterValue> {
-        let id = state.id();
-
-        let mut buf = Vec::new();
-        source.read_to_end(&mut buf)?;
-
-        let image =
-            image::io::Reader::with_format(std::io::Cursor::new(buf), image::ImageFormat::Png)
-                .decode()
-                .map_err(|e| Box::new(e) as Box<dyn std::error::Error + Send>)?
-                .to_rgba8();
+impl SimpleImporter for ImageImporter {
+    type Asset = Image;
 
-        let data = Image(image);
+    fn import(&self, bytes: &[u8], _: &mut ImportContext) -> distill_importer::Result<Image> {
+        let image = image::load_from_memory_with_format(bytes, image::ImageFormat::Png)
+            .map_err(|e| Box::new(e) as Box<dyn std::error::Error + Send>)?
+            .to_rgba8();
 
-        Ok(ImporterValue {
-            assets: vec![ImportedAsset {
-                id,
-                search_tags: vec![],
-                build_deps: vec![],
-                load_deps: vec![],
-                build_pipeline: None,
-                asset_data: Box::new(data),
-            }],
-        })
+        Ok(Image(image))
     }
 }
 
@@ -84,7 +48,7 @@ fn main() {
     App::new()
         .add_plugin(ScheduleRunnerPlugin::default())
         .add_plugin(LogPlugin::default())
-        .add_asset_loader(&["png"], ImageImporter)
+        .add_asset_loader(&["png"], Simple(ImageImporter))
         .add_plugin(AssetPlugin)
         .add_asset::<Image>()
         .add_startup_system(setup)
@@ -0,0 +1,88 @@
+use std::collections::BTreeMap;
+
+use bevy_app::prelude::*;
+use bevy_app::{AppExit, ScheduleRunnerPlugin};
+use bevy_asset::importer::text_importer::Text;
+use bevy_asset::importer::{TextImporter, TomlImporter, YamlImporter};
+use bevy_asset::prelude::*;
+use bevy_ecs::prelude::*;
+use bevy_log::prelude::*;
+use bevy_log::LogPlugin;
+
+#[derive(Serialize, Deserialize, TypeUuid, Debug)]
+#[uuid = "3b8c2b3e-6f0a-4a55-9a3e-0c5c8a9b1f27"]
+pub struct Balance {
+    description: Handle<Text>,
+    units: Vec<Unit>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Unit {
+    name: String,
+    health: u32,
+    damage: u32,
+}
+
+#[derive(Serialize, Deserialize, TypeUuid, Debug)]
+#[uuid = "b7e0d7a4-2c55-4f7c-8f0e-5d1b6c3a9e42"]
+pub struct Localization {
+    language: String,
+    strings: BTreeMap<String, String>,
+}
+
+fn main() {
+    App::new()
+        .add_plugin(ScheduleRunnerPlugin::default())
+        .add_plugin(LogPlugin)
+        .insert_resource(AssetServerSettings::Directory(DirectorySettings::new(
+            "assets",
+        )))
+        .add_plugin(AssetPlugin)
+        .add_asset_loader(&["toml"], TomlImporter::<Balance>::new())
+        .add_asset_loader(&["yaml"], YamlImporter::<Localization>::new())
+        .add_asset_loader(&["txt"], TextImporter)
+        .add_asset::<Balance>()
+        .add_asset::<Localization>()
+        .add_asset::<Text>()
+        .add_startup_system(setup)
+        .add_system(system)
+        .run();
+}
+
+struct Handles {
+    balance: Handle<Balance>,
+    localization: Handle<Localization>,
+}
+
+fn setup(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.insert_resource(Handles {
+        balance: asset_server.load("config/balance.toml"),
+        localization: asset_server.load("config/localization.yaml"),
+    });
+}
+
+fn system(
+    handles: Res<Handles>,
+    balances: Res<Assets<Balance>>,
+    localizations: Res<Assets<Localization>>,
+    texts: Res<Assets<Text>>,
+    mut app_exit: EventWriter<AppExit>,
+) {
+    let (balance, localization) = match (
+        balances.get(&handles.balance),
+        localizations.get(&handles.localization),
+    ) {
+        (Some(balance), Some(localization)) => (balance, localization),
+        _ => return,
+    };
+    // the handle in the TOML file was resolved by path
+    let description = match texts.get(&balance.description) {
+        Some(description) => description,
+        None => return,
+    };
+
+    info!("{:?}", balance.units);
+    info!("{}...", &description[..50]);
+    info!("{:?}", localization);
+    app_exit.send(AppExit);
+}
@@ -0,0 +1,63 @@
+use bevy_app::prelude::*;
+use bevy_app::{AppExit, ScheduleRunnerPlugin};
+use bevy_asset::importer::text_importer::Text;
+use bevy_asset::importer::{RonImporter, TextImporter};
+use bevy_asset::prelude::*;
+use bevy_ecs::prelude::*;
+use bevy_log::prelude::*;
+use bevy_log::LogPlugin;
+
+#[derive(Serialize, Deserialize, TypeUuid, Debug)]
+#[uuid = "fab4249b-f95d-411d-a017-7549df090a4f"]
+pub struct CustomAsset {
+    pub cool_string: String,
+}
+
+fn main() {
+    App::new()
+        .add_plugin(ScheduleRunnerPlugin::default())
+        .add_plugin(LogPlugin)
+        .insert_resource(AssetServerSettings::Directory(
+            DirectorySettings::new("assets").with_watch(true),
+        ))
+        .add_plugin(AssetPlugin)
+        .add_asset_loader(&["casset"], RonImporter::<CustomAsset>::new())
+        .add_asset_loader(&["txt"], TextImporter)
+        .add_asset::<CustomAsset>()
+        .add_asset::<Text>()
+        .add_startup_system(setup)
+        .add_system(system)
+        .run();
+}
+
+struct Handles {
+    custom_asset: Handle<CustomAsset>,
+    text: Handle<Text>,
+}
+
+fn setup(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.insert_resource(Handles {
+        custom_asset: asset_server.load("custom_asset.casset"),
+        // the same file as `lorem_ipsum.txt`, loaded by the UUID from its .meta file
+        text: asset_server.load("1d44085f-d8c4-4a2f-a4c1-c26c3e2902f1"),
+    });
+}
+
+fn system(
+    handles: Res<Handles>,
+    custom_assets: Res<Assets<CustomAsset>>,
+    texts: Res<Assets<Text>>,
+    mut app_exit: EventWriter<AppExit>,
+) {
+    let (custom_asset, text) = match (
+        custom_assets.get(&handles.custom_asset),
+        texts.get(&handles.text),
+    ) {
+        (Some(custom_asset), Some(text)) => (custom_asset, text),
+        _ => return,
+    };
+
+    info!("{:?}", custom_asset);
+    info!("{}...", &text[..50]);
+    app_exit.send(AppExit);
+}
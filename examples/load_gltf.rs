@@ -0,0 +1,71 @@
+use bevy_app::prelude::*;
+use bevy_app::{AppExit, ScheduleRunnerPlugin};
+use bevy_asset::importer::{
+    Gltf, GltfImporter, GltfMaterial, GltfMesh, GltfScene, Image, ImageImporter,
+};
+use bevy_asset::prelude::*;
+use bevy_ecs::prelude::*;
+use bevy_log::prelude::*;
+use bevy_log::LogPlugin;
+
+fn main() {
+    App::new()
+        .add_plugin(ScheduleRunnerPlugin::default())
+        .add_plugin(LogPlugin)
+        .insert_resource(AssetServerSettings::Directory(DirectorySettings::new(
+            "assets",
+        )))
+        .add_plugin(AssetPlugin)
+        .add_asset_loader(&["gltf", "glb"], GltfImporter)
+        .add_asset_loader(&["png", "jpg", "jpeg", "bmp"], ImageImporter)
+        .add_asset::<Gltf>()
+        .add_asset::<GltfScene>()
+        .add_asset::<GltfMesh>()
+        .add_asset::<GltfMaterial>()
+        .add_asset::<Image>()
+        .add_startup_system(setup)
+        .add_system(system)
+        .run();
+}
+
+struct MeshHandle(Handle<GltfMesh>);
+
+fn setup(mut commands: Commands, asset_server: Res<AssetServer>) {
+    // a sub-asset of the glTF file, its material and the texture file are loaded as its dependencies
+    let mesh = asset_server.load(format!("triangle.gltf#Mesh0@{}", GltfMesh::TYPE_UUID).as_str());
+    commands.insert_resource(MeshHandle(mesh));
+}
+
+fn system(
+    mesh: Res<MeshHandle>,
+    meshes: Res<Assets<GltfMesh>>,
+    materials: Res<Assets<GltfMaterial>>,
+    images: Res<Assets<Image>>,
+    mut app_exit: EventWriter<AppExit>,
+) {
+    let mesh = match meshes.get(&mesh.0) {
+        Some(mesh) => mesh,
+        None => return,
+    };
+    let primitive = &mesh.primitives[0];
+    let material = match primitive.material.as_ref().and_then(|m| materials.get(m)) {
+        Some(material) => material,
+        None => return,
+    };
+    let texture = match material
+        .base_color_texture
+        .as_ref()
+        .and_then(|t| images.get(t))
+    {
+        Some(texture) => texture,
+        None => return,
+    };
+
+    info!(
+        "mesh with {} vertices, base color texture is {}x{}",
+        primitive.positions.len(),
+        texture.width,
+        texture.height
+    );
+    app_exit.send(AppExit);
+}
@@ -0,0 +1,51 @@
+use bevy_app::prelude::*;
+use bevy_app::{AppExit, ScheduleRunnerPlugin};
+use bevy_asset::importer::{Image, ImageFormat, ImageImporter};
+use bevy_asset::prelude::*;
+use bevy_ecs::prelude::*;
+use bevy_log::prelude::*;
+use bevy_log::LogPlugin;
+
+fn main() {
+    App::new()
+        .add_plugin(ScheduleRunnerPlugin::default())
+        .add_plugin(LogPlugin)
+        .insert_resource(AssetServerSettings::Directory(DirectorySettings::new(
+            "assets",
+        )))
+        .add_plugin(AssetPlugin)
+        .add_asset_loader(&["png", "jpg", "jpeg", "bmp"], ImageImporter)
+        .add_asset::<Image>()
+        .add_startup_system(setup)
+        .add_system(system)
+        .run();
+}
+
+struct ImageHandle(Handle<Image>);
+
+fn setup(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.insert_resource(ImageHandle(asset_server.load("base_color.png")));
+}
+
+fn system(
+    handle: Res<ImageHandle>,
+    images: Res<Assets<Image>>,
+    mut app_exit: EventWriter<AppExit>,
+) {
+    let image = match images.get(&handle.0) {
+        Some(image) => image,
+        None => return,
+    };
+
+    assert_eq!((image.width, image.height), (128, 128));
+    assert_eq!(image.format, ImageFormat::Rgba8UnormSrgb);
+    assert_eq!(image.data.len(), 128 * 128 * 4);
+    info!(
+        "base_color.png is {}x{}, the top left pixel is {:?}",
+        image.width,
+        image.height,
+        image.pixel(0, 0)
+    );
+
+    app_exit.send(AppExit);
+}
@@ -0,0 +1,72 @@
+use bevy_app::prelude::*;
+use bevy_app::{AppExit, ScheduleRunnerPlugin};
+use bevy_asset::prelude::*;
+use bevy_ecs::prelude::*;
+use bevy_log::prelude::*;
+use bevy_log::LogPlugin;
+use distill_core::AssetUuid;
+
+#[derive(Serialize, Deserialize, TypeUuid, Debug)]
+#[uuid = "fab4249b-f95d-411d-a017-7549df090a4f"]
+pub struct CustomAsset {
+    pub cool_string: String,
+}
+
+const CUSTOM_ASSET: AssetUuid = AssetUuid([1; 16]);
+
+fn main() {
+    let source = MemoryAssetSource::new();
+    source.insert_serialized(
+        CUSTOM_ASSET,
+        "custom_asset.casset",
+        &CustomAsset {
+            cool_string: "loaded from memory".to_string(),
+        },
+    );
+
+    App::new()
+        .add_plugin(ScheduleRunnerPlugin::default())
+        .add_plugin(LogPlugin)
+        .insert_resource(AssetServerSettings::InMemory(source.clone()))
+        .insert_resource(source)
+        .add_plugin(AssetPlugin)
+        .add_asset::<CustomAsset>()
+        .add_startup_system(setup)
+        .add_system(system)
+        .run();
+}
+
+struct CustomAssetHandle(Handle<CustomAsset>);
+
+fn setup(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.insert_resource(CustomAssetHandle(asset_server.load("custom_asset.casset")));
+}
+
+fn system(
+    source: Res<MemoryAssetSource>,
+    handle: Res<CustomAssetHandle>,
+    custom_assets: Res<Assets<CustomAsset>>,
+    mut events: EventReader<AssetEvent<CustomAsset>>,
+    mut app_exit: EventWriter<AppExit>,
+    mut reloaded: Local<bool>,
+) {
+    for event in events.iter() {
+        if let AssetEvent::Modified { .. } = event {
+            info!("{:?}", custom_assets.get(&handle.0));
+
+            if !*reloaded {
+                *reloaded = true;
+                // replacing the asset reloads it, like a changed file would
+                source.insert_serialized(
+                    CUSTOM_ASSET,
+                    "custom_asset.casset",
+                    &CustomAsset {
+                        cool_string: "reloaded from memory".to_string(),
+                    },
+                );
+            } else {
+                app_exit.send(AppExit);
+            }
+        }
+    }
+}
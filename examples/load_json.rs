@@ -0,0 +1,45 @@
+use bevy_app::prelude::*;
+use bevy_app::{AppExit, ScheduleRunnerPlugin};
+use bevy_asset::importer::JsonImporter;
+use bevy_asset::prelude::*;
+use bevy_ecs::prelude::*;
+use bevy_log::prelude::*;
+use bevy_log::LogPlugin;
+
+#[derive(Serialize, Deserialize, TypeUuid, Debug)]
+#[uuid = "fab4249b-f95d-411d-a017-7549df090a4f"]
+pub struct CustomAsset {
+    pub cool_string: String,
+}
+
+fn main() {
+    App::new()
+        .add_plugin(ScheduleRunnerPlugin::default())
+        .add_plugin(LogPlugin)
+        .insert_resource(AssetServerSettings::Directory(DirectorySettings::new(
+            "assets",
+        )))
+        .add_plugin(AssetPlugin)
+        .add_asset_loader(&["json"], JsonImporter::<CustomAsset>::new())
+        .add_asset::<CustomAsset>()
+        .add_startup_system(setup)
+        .add_system(system)
+        .run();
+}
+
+struct CustomAssetHandle(Handle<CustomAsset>);
+
+fn setup(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.insert_resource(CustomAssetHandle(asset_server.load("custom_asset.json")));
+}
+
+fn system(
+    handle: Res<CustomAssetHandle>,
+    custom_assets: Res<Assets<CustomAsset>>,
+    mut app_exit: EventWriter<AppExit>,
+) {
+    if let Some(custom_asset) = custom_assets.get(&handle.0) {
+        info!("{:?}", custom_asset);
+        app_exit.send(AppExit);
+    }
+}
@@ -0,0 +1,43 @@
+use bevy_app::prelude::*;
+use bevy_app::{AppExit, ScheduleRunnerPlugin};
+use bevy_asset::importer::{ShaderSource, ShaderSourceImporter};
+use bevy_asset::prelude::*;
+use bevy_ecs::prelude::*;
+use bevy_log::prelude::*;
+use bevy_log::LogPlugin;
+
+fn main() {
+    App::new()
+        .add_plugin(ScheduleRunnerPlugin::default())
+        .add_plugin(LogPlugin)
+        .insert_resource(AssetServerSettings::Directory(
+            // changing `shader_common.wgsl` imports `shader.wgsl` again
+            DirectorySettings::new("assets").with_watch(true),
+        ))
+        .add_plugin(AssetPlugin)
+        .add_asset_loader(&["wgsl"], ShaderSourceImporter)
+        .add_asset::<ShaderSource>()
+        .add_startup_system(setup)
+        .add_system(system)
+        .run();
+}
+
+struct ShaderHandle(Handle<ShaderSource>);
+
+fn setup(mut commands: Commands, asset_server: Res<AssetServer>) {
+    // the included shader is loaded as a dependency
+    commands.insert_resource(ShaderHandle(asset_server.load("shader.wgsl")));
+}
+
+fn system(
+    shader: Res<ShaderHandle>,
+    shaders: Res<Assets<ShaderSource>>,
+    mut app_exit: EventWriter<AppExit>,
+) {
+    match ShaderSource::resolve(&shader.0, &shaders) {
+        Ok(source) => info!("{}", source),
+        // the shader or its includes may still be loading
+        Err(_) => return,
+    }
+    app_exit.send(AppExit);
+}
@@ -21,6 +21,7 @@ fn main() {
         .add_asset::<CustomAsset>()
         .add_startup_system(setup)
         .add_system(system)
+        .add_system(connection_status)
         .run();
 }
 
@@ -62,3 +63,14 @@ fn system(
         *has_printed = true;
     }
 }
+
+fn connection_status(mut events: EventReader<AssetConnectionEvent>) {
+    for event in events.iter() {
+        match event {
+            AssetConnectionEvent::Disconnected => warn!("asset server disconnected"),
+            AssetConnectionEvent::Connected | AssetConnectionEvent::Reconnected => {
+                info!("asset server connected")
+            }
+        }
+    }
+}
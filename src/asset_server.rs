@@ -1,17 +1,34 @@
 use std::str::FromStr;
-use std::sync::Arc;
+#[cfg(feature = "packfile")]
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, RwLock};
 
+#[cfg(feature = "packfile")]
+use bevy_tasks::TaskPool;
+use bevy_utils::HashMap;
 use distill_core::{AssetTypeId, AssetUuid};
+use distill_importer::BoxedImporter;
 use distill_loader::crossbeam_channel::Sender;
 use distill_loader::handle::{AssetHandle, RefOp};
 use distill_loader::storage::{IndirectIdentifier, LoadInfo, LoadStatus};
 use distill_loader::{LoadHandle, Loader};
 
+use crate::io::import::{self, SharedImporters};
+#[cfg(feature = "packfile")]
+use crate::io::{packfile_layer, MountCommand, PackfileId};
 use crate::prelude::*;
 
 pub struct AssetServer {
     loader: Loader,
     refop_sender: Arc<Sender<RefOp>>,
+    importers: SharedImporters,
+    load_priorities: RwLock<HashMap<LoadHandle, i32>>,
+    #[cfg(feature = "packfile")]
+    mount_sender: Option<Sender<MountCommand>>,
+    #[cfg(feature = "packfile")]
+    io_task_pool: Option<TaskPool>,
+    #[cfg(feature = "packfile")]
+    next_packfile_id: AtomicU32,
 }
 
 #[derive(Debug, Clone)]
@@ -91,9 +108,33 @@ impl AssetServer {
         AssetServer {
             loader,
             refop_sender,
+            importers: SharedImporters::default(),
+            load_priorities: RwLock::default(),
+            #[cfg(feature = "packfile")]
+            mount_sender: None,
+            #[cfg(feature = "packfile")]
+            io_task_pool: None,
+            #[cfg(feature = "packfile")]
+            next_packfile_id: AtomicU32::new(0),
         }
     }
 
+    pub(crate) fn with_importers(mut self, importers: SharedImporters) -> AssetServer {
+        self.importers = importers;
+        self
+    }
+
+    #[cfg(feature = "packfile")]
+    pub(crate) fn with_mount_sender(
+        mut self,
+        mount_sender: Sender<MountCommand>,
+        io_task_pool: TaskPool,
+    ) -> AssetServer {
+        self.mount_sender = Some(mount_sender);
+        self.io_task_pool = Some(io_task_pool);
+        self
+    }
+
     pub fn loader(&self) -> &Loader {
         &self.loader
     }
@@ -119,6 +160,21 @@ impl AssetServer {
         }
     }
 
+    /// Registers an importer while the app is running, like [`AddAsset::add_asset_loader`].
+    ///
+    /// Files with one of the `extensions` which were skipped so far are imported:
+    /// an asset daemon started by the [`AssetPlugin`] is restarted with the new importer,
+    /// and directory sources are scanned again.
+    ///
+    /// If a different importer with the same UUID is registered already, `importer` is rejected with an error.
+    pub fn register_importer<T: BoxedImporter>(
+        &self,
+        extensions: &'static [&'static str],
+        importer: T,
+    ) {
+        import::register_importer(&self.importers, extensions, Arc::new(importer));
+    }
+
     pub fn get_load_status<A: AssetHandle>(&self, handle: A) -> LoadStatus {
         self.loader.get_load_status(handle.load_handle())
     }
@@ -126,6 +182,74 @@ impl AssetServer {
     pub fn get_load_info<A: AssetHandle>(&self, handle: A) -> Option<LoadInfo> {
         self.loader.get_load_info(handle.load_handle())
     }
+
+    /// Assets with a higher priority are handed to their [`Assets`] first when the
+    /// [`AssetLoadBudget`] doesn't allow handling every loaded asset in one frame. The default priority is 0.
+    pub fn set_load_priority<A: AssetHandle>(&self, handle: A, priority: i32) {
+        let mut load_priorities = self.load_priorities.write().unwrap();
+        if priority == 0 {
+            load_priorities.remove(&handle.load_handle());
+        } else {
+            load_priorities.insert(handle.load_handle(), priority);
+        }
+    }
+
+    /// The load priorities by the handle the asset is stored under, with indirect handles resolved.
+    pub(crate) fn resolved_load_priorities(&self) -> HashMap<LoadHandle, i32> {
+        let indirection_table = self.loader.indirection_table();
+        self.load_priorities
+            .read()
+            .unwrap()
+            .iter()
+            .filter_map(|(&load_handle, &priority)| {
+                let load_handle = if load_handle.is_indirect() {
+                    indirection_table.resolve(load_handle)?
+                } else {
+                    load_handle
+                };
+                Some((load_handle, priority))
+            })
+            .collect()
+    }
+}
+
+#[cfg(feature = "packfile")]
+impl AssetServer {
+    /// Adds a packfile on top of the existing asset sources, for example for DLC or patches.
+    ///
+    /// The packfile is opened on the [`IoTaskPool`](bevy_tasks::IoTaskPool) and mounted at the start of the frame after that.
+    /// Assets in the packfile shadow the ones with the same UUID or path from previously mounted sources
+    /// and are reloaded once it is mounted. A packfile which can't be read is logged as an error and never mounted.
+    pub fn mount_packfile(&self, settings: &PackfileSettings) -> PackfileId {
+        let id = PackfileId(self.next_packfile_id.fetch_add(1, Ordering::Relaxed));
+        let mount_sender = self.mount_sender().clone();
+        let settings = settings.clone();
+        self.io_task_pool
+            .as_ref()
+            .unwrap()
+            .spawn(async move {
+                // fails only once the app is gone
+                let _ = mount_sender.send(MountCommand::Mount(id, packfile_layer(&settings)));
+            })
+            .detach();
+        id
+    }
+
+    /// Removes a packfile mounted with [`mount_packfile`](Self::mount_packfile).
+    ///
+    /// Its assets are reloaded from the remaining sources. Assets which aren't available anywhere else
+    /// are removed from their [`Assets`](crate::Assets) collection, which sends an [`AssetEvent::Removed`](crate::AssetEvent::Removed).
+    pub fn unmount_packfile(&self, id: PackfileId) {
+        self.mount_sender()
+            .send(MountCommand::Unmount(id))
+            .expect("asset loader IO was dropped");
+    }
+
+    fn mount_sender(&self) -> &Sender<MountCommand> {
+        self.mount_sender
+            .as_ref()
+            .expect("packfiles can only be mounted on the `AssetServer` of the `AssetPlugin`")
+    }
 }
 
 impl AssetServer {
@@ -0,0 +1,37 @@
+//! Builds a packfile from asset directories with the importers of `bevy_asset`.
+//!
+//! Usage: `bevy-asset-pack [--deterministic-uuids] <OUT_PATH> <ASSET_DIR>...`
+
+use std::path::PathBuf;
+use std::process::exit;
+
+use bevy_asset::importer::builtin_importers;
+use bevy_asset::packfile;
+
+fn main() {
+    let mut args: Vec<PathBuf> = std::env::args_os().skip(1).map(PathBuf::from).collect();
+    let deterministic_uuids = match args.first() {
+        Some(flag) if flag.as_os_str() == "--deterministic-uuids" => {
+            args.remove(0);
+            true
+        }
+        _ => false,
+    };
+    let (out_path, asset_dirs) = match args.split_first() {
+        Some((out_path, asset_dirs)) if !asset_dirs.is_empty() => (out_path, asset_dirs),
+        _ => {
+            eprintln!("usage: bevy-asset-pack [--deterministic-uuids] <OUT_PATH> <ASSET_DIR>...");
+            exit(2);
+        }
+    };
+
+    let importers = builtin_importers();
+
+    match packfile::build(asset_dirs, &importers, out_path, deterministic_uuids) {
+        Ok(count) => println!("wrote {} assets to {}", count, out_path.display()),
+        Err(e) => {
+            eprintln!("error: {}", e);
+            exit(1);
+        }
+    }
+}
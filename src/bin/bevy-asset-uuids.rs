@@ -0,0 +1,34 @@
+//! Reports assets whose UUID in the `.meta` file differs from the one derived from their path,
+//! with the importers of `bevy_asset`.
+//!
+//! Usage: `bevy-asset-uuids <ASSET_DIR>...`
+
+use std::path::PathBuf;
+use std::process::exit;
+
+use bevy_asset::importer::builtin_importers;
+use bevy_asset::uuids;
+
+fn main() {
+    let asset_dirs: Vec<PathBuf> = std::env::args_os().skip(1).map(PathBuf::from).collect();
+    if asset_dirs.is_empty() {
+        eprintln!("usage: bevy-asset-uuids <ASSET_DIR>...");
+        exit(2);
+    }
+
+    let importers = builtin_importers();
+
+    match uuids::check(&asset_dirs, &importers) {
+        Ok(mismatches) if mismatches.is_empty() => println!("all UUIDs match their paths"),
+        Ok(mismatches) => {
+            for mismatch in &mismatches {
+                println!("{}", mismatch);
+            }
+            exit(1);
+        }
+        Err(e) => {
+            eprintln!("error: {}", e);
+            exit(1);
+        }
+    }
+}
@@ -0,0 +1,274 @@
+use std::fmt;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use bevy_app::prelude::*;
+use bevy_app::AppExit;
+use bevy_ecs::prelude::*;
+use distill_importer::BoxedImporter;
+use distill_loader::crossbeam_channel::{bounded, Receiver};
+
+use crate::io::import;
+use crate::settings::AssetDaemonSettings;
+
+/// How long [`AssetDaemonHandle::stop`] waits for the daemon to flush its database and exit.
+const STOP_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Events about the asset daemon thread started by the [`AssetPlugin`](crate::AssetPlugin).
+#[derive(Debug, Clone)]
+pub enum AssetDaemonEvent {
+    /// The daemon thread exited without being asked to.
+    Stopped,
+    /// The daemon was restarted with changed importers, schemas or processors and is running again.
+    Restarted,
+    /// The daemon thread panicked.
+    Failed(String),
+}
+
+#[derive(Debug)]
+pub enum AssetDaemonError {
+    AlreadyStopped,
+    /// The daemon didn't react to the shutdown signal, its thread is left running.
+    NotResponding,
+    Panicked(String),
+}
+
+impl fmt::Display for AssetDaemonError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AssetDaemonError::AlreadyStopped => write!(f, "the asset daemon was already stopped"),
+            AssetDaemonError::NotResponding => {
+                write!(
+                    f,
+                    "the asset daemon didn't shut down within {:?}",
+                    STOP_TIMEOUT
+                )
+            }
+            AssetDaemonError::Panicked(msg) => write!(f, "the asset daemon panicked: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for AssetDaemonError {}
+
+/// Owns a running asset daemon.
+///
+/// The [`AssetPlugin`](crate::AssetPlugin) inserts this as a resource when using
+/// [`AssetServerSettings::Daemon`](crate::AssetServerSettings::Daemon) and stops it on [`AppExit`].
+/// Dropping the handle stops the daemon as well, which closes its database and frees the address,
+/// so daemons can be started and stopped repeatedly inside of one process.
+pub struct AssetDaemonHandle {
+    thread: Option<JoinHandle<()>>,
+    /// Sends the shutdown signal, returns whether the daemon received it.
+    shutdown: Option<Box<dyn FnOnce() -> bool + Send + Sync>>,
+    /// See [`import::importers_version`].
+    importers_version: u64,
+    /// Receives the new daemon while restarting in the background.
+    restart: Option<Receiver<AssetDaemonHandle>>,
+}
+
+impl AssetDaemonHandle {
+    pub fn start(
+        settings: &AssetDaemonSettings,
+        importers: Vec<(&'static [&'static str], Box<dyn BoxedImporter>)>,
+    ) -> AssetDaemonHandle {
+        let importers_version = import::importers_version(&importers);
+        let (thread, shutdown) = settings.daemon(importers).run();
+        bevy_log::debug!("started asset daemon at {}", settings.address());
+
+        AssetDaemonHandle {
+            thread: Some(thread),
+            shutdown: Some(Box::new(move || shutdown.send(true).is_ok())),
+            importers_version,
+            restart: None,
+        }
+    }
+
+    /// Identifies the importers the daemon was started with, see [`import::importers_version`].
+    pub(crate) fn importers_version(&self) -> u64 {
+        self.importers_version
+    }
+
+    /// Stops the daemon and starts it again with a different set of importers.
+    ///
+    /// Stopping can take a while, so it happens on a background thread. The daemon isn't
+    /// [running](Self::is_running) until [`AssetDaemonEvent::Restarted`] is sent.
+    pub(crate) fn restart(
+        &mut self,
+        settings: &AssetDaemonSettings,
+        importers: Vec<(&'static [&'static str], Box<dyn BoxedImporter>)>,
+    ) {
+        let mut old = std::mem::replace(
+            self,
+            AssetDaemonHandle {
+                thread: None,
+                shutdown: None,
+                importers_version: import::importers_version(&importers),
+                restart: None,
+            },
+        );
+        let settings = settings.clone();
+        let (sender, receiver) = bounded(1);
+        std::thread::Builder::new()
+            .name("asset daemon restart".to_string())
+            .spawn(move || {
+                if let Err(e) = old.stop() {
+                    bevy_log::error!("failed to stop asset daemon: {}", e);
+                }
+                let _ = sender.send(AssetDaemonHandle::start(&settings, importers));
+            })
+            .expect("failed to spawn asset daemon restart thread");
+        self.restart = Some(receiver);
+    }
+
+    pub fn is_restarting(&self) -> bool {
+        self.restart.is_some()
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.thread
+            .as_ref()
+            .map_or(false, |thread| !thread.is_finished())
+    }
+
+    /// Signals the daemon to shut down and waits for its thread to exit.
+    ///
+    /// Gives up after a timeout instead of blocking forever when the daemon doesn't react,
+    /// in which case its thread keeps running in the background.
+    pub fn stop(&mut self) -> Result<(), AssetDaemonError> {
+        if let Some(restart) = self.restart.take() {
+            // the restart stops the old daemon first, which gives up after the timeout as well
+            let new = restart
+                .recv_timeout(STOP_TIMEOUT * 2)
+                .map_err(|_| AssetDaemonError::NotResponding)?;
+            *self = new;
+        }
+        let thread = self.thread.take().ok_or(AssetDaemonError::AlreadyStopped)?;
+        let signaled = self.shutdown.take().map_or(false, |shutdown| shutdown());
+        if !signaled && !thread.is_finished() {
+            return Err(AssetDaemonError::NotResponding);
+        }
+        let deadline = Instant::now() + STOP_TIMEOUT;
+        while !thread.is_finished() {
+            if Instant::now() >= deadline {
+                return Err(AssetDaemonError::NotResponding);
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        thread
+            .join()
+            .map_err(|panic| AssetDaemonError::Panicked(panic_message(panic)))
+    }
+
+    /// Returns an event if a restart finished since the last call.
+    fn poll_restart(&mut self) -> Option<AssetDaemonEvent> {
+        let new = self.restart.as_ref()?.try_recv().ok()?;
+        self.restart = None;
+        *self = new;
+        Some(AssetDaemonEvent::Restarted)
+    }
+
+    /// Returns an event if the daemon thread exited since the last call.
+    fn poll_exit(&mut self) -> Option<AssetDaemonEvent> {
+        if self.is_running() {
+            return None;
+        }
+        let thread = self.thread.take()?;
+        self.shutdown = None;
+        match thread.join() {
+            Ok(()) => Some(AssetDaemonEvent::Stopped),
+            Err(panic) => Some(AssetDaemonEvent::Failed(panic_message(panic))),
+        }
+    }
+}
+
+impl Drop for AssetDaemonHandle {
+    fn drop(&mut self) {
+        if self.thread.is_some() || self.restart.is_some() {
+            if let Err(e) = self.stop() {
+                bevy_log::error!("{}", e);
+            }
+        }
+    }
+}
+
+fn panic_message(panic: Box<dyn std::any::Any + Send>) -> String {
+    match panic.downcast::<String>() {
+        Ok(msg) => *msg,
+        Err(panic) => match panic.downcast::<&'static str>() {
+            Ok(msg) => msg.to_string(),
+            Err(_) => "unknown panic payload".to_string(),
+        },
+    }
+}
+
+pub(crate) fn monitor_asset_daemon(
+    mut daemon: ResMut<AssetDaemonHandle>,
+    mut events: EventWriter<AssetDaemonEvent>,
+) {
+    if let Some(event) = daemon.poll_restart() {
+        bevy_log::info!("restarted asset daemon");
+        events.send(event);
+    } else if let Some(event) = daemon.poll_exit() {
+        bevy_log::error!("asset daemon exited: {:?}", event);
+        events.send(event);
+    }
+}
+
+pub(crate) fn stop_asset_daemon_on_exit(
+    mut app_exit: EventReader<AppExit>,
+    daemon: Option<ResMut<AssetDaemonHandle>>,
+) {
+    if app_exit.iter().next().is_none() {
+        return;
+    }
+    if let Some(mut daemon) = daemon {
+        if daemon.is_running() || daemon.is_restarting() {
+            if let Err(e) = daemon.stop() {
+                bevy_log::error!("failed to stop asset daemon: {}", e);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn start_and_stop_repeatedly() {
+        let dir = std::env::temp_dir().join(format!("bevy_asset_daemon_{}", std::process::id()));
+        let asset_dir = dir.join("assets");
+        std::fs::create_dir_all(&asset_dir).unwrap();
+        // a free port, so parallel test runs and a running daemon don't get in the way
+        let port = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let settings = AssetDaemonSettings::builder()
+            .asset_dir(&asset_dir)
+            .db_path(dir.join("db"))
+            .port(port)
+            .build()
+            .unwrap();
+
+        // every daemon has to release the database and the address for the next one
+        for _ in 0..3 {
+            let mut daemon = AssetDaemonHandle::start(&settings, Vec::new());
+            assert!(daemon.is_running());
+            daemon.stop().unwrap();
+            assert!(!daemon.is_running());
+            assert!(matches!(
+                daemon.stop(),
+                Err(AssetDaemonError::AlreadyStopped)
+            ));
+        }
+        drop(AssetDaemonHandle::start(&settings, Vec::new()));
+        AssetDaemonHandle::start(&settings, Vec::new())
+            .stop()
+            .unwrap();
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -9,8 +9,11 @@ use bevy_ecs::prelude::Component;
 use distill_loader::crossbeam_channel::Sender;
 use distill_loader::handle::{self, AssetHandle, RefOp};
 use distill_loader::LoadHandle;
+use serde::de::value::StringDeserializer;
 use serde::Serialize;
 
+use crate::util;
+
 #[derive(Component)]
 pub struct Handle<A: Asset>(handle::Handle<A>);
 
@@ -64,10 +67,44 @@ impl<'de, A: Asset> Deserialize<'de> for Handle<A> {
     where
         D: serde::Deserializer<'de>,
     {
-        handle::Handle::deserialize(deserializer).map(Handle)
+        deserialize_asset_ref(deserializer).map(Handle)
     }
 }
 
+/// Creates a handle while importing, from a UUID, a path or a `#label` of a sibling sub-asset.
+///
+/// Like a deserialized handle, it is recorded for [`collect_load_deps`](crate::util::collect_load_deps).
+pub(crate) fn import_handle<A: Asset>(
+    asset_ref: &str,
+) -> Result<Handle<A>, serde::de::value::Error> {
+    Handle::deserialize(StringDeserializer::new(asset_ref.to_string()))
+}
+
+/// Deserializes a handle, resolving `#label` references to sibling sub-assets
+/// while an importer runs [`with_sibling_labels`](crate::util::with_sibling_labels)
+/// and recording it for [`collect_load_deps`](crate::util::collect_load_deps).
+fn deserialize_asset_ref<'de, D, H>(deserializer: D) -> Result<H, D::Error>
+where
+    D: serde::Deserializer<'de>,
+    H: Deserialize<'de>,
+{
+    let importing = util::resolving_sibling_labels() || util::collecting_load_deps();
+    if !importing || !deserializer.is_human_readable() {
+        return H::deserialize(deserializer);
+    }
+
+    let asset_ref = String::deserialize(deserializer)?;
+    let asset_ref = match asset_ref
+        .strip_prefix('#')
+        .and_then(util::reference_sibling_label)
+    {
+        Some(id) => uuid::Uuid::from_bytes(id.0).to_string(),
+        None => asset_ref,
+    };
+    util::record_load_dep(&asset_ref);
+    H::deserialize(StringDeserializer::<D::Error>::new(asset_ref))
+}
+
 impl<A: Asset> bevy_reflect::GetTypeRegistration for Handle<A> {
     fn get_type_registration() -> bevy_reflect::TypeRegistration {
         let registration = bevy_reflect::TypeRegistration::of::<Handle<A>>();
@@ -154,7 +191,7 @@ impl<'de> Deserialize<'de> for HandleUntyped {
     where
         D: serde::Deserializer<'de>,
     {
-        handle::GenericHandle::deserialize(deserializer).map(HandleUntyped)
+        deserialize_asset_ref(deserializer).map(HandleUntyped)
     }
 }
 
@@ -0,0 +1,62 @@
+use std::ops::Deref;
+
+use crate::prelude::*;
+use crate::util::AssetUuidImporterState;
+use distill_importer::{ImportedAsset, Importer, ImporterValue};
+
+/// Imports files as they are, for fonts, audio or custom binary formats that are decoded at runtime.
+#[derive(TypeUuid)]
+#[uuid = "f611c30c-f58e-47c7-b952-8288ba3f2132"]
+pub struct BytesImporter;
+
+#[derive(TypeUuid, Serialize, Deserialize)]
+#[serde(transparent)]
+#[uuid = "79e31399-03b4-4b14-879a-aa581396e9c8"]
+pub struct Bytes(pub Vec<u8>);
+impl Deref for Bytes {
+    type Target = [u8];
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl Importer for BytesImporter {
+    fn version_static() -> u32
+    where
+        Self: Sized,
+    {
+        1
+    }
+
+    fn version(&self) -> u32 {
+        Self::version_static()
+    }
+
+    type Options = ();
+    type State = AssetUuidImporterState;
+
+    fn import(
+        &self,
+        _: &mut distill_importer::ImportOp,
+        source: &mut dyn std::io::Read,
+        _: &Self::Options,
+        state: &mut Self::State,
+    ) -> Result<ImporterValue, distill_importer::Error> {
+        let mut bytes = Vec::new();
+        source.read_to_end(&mut bytes)?;
+
+        let id = state.id();
+
+        Ok(ImporterValue {
+            assets: vec![ImportedAsset {
+                id,
+                search_tags: vec![],
+                build_deps: vec![],
+                load_deps: vec![],
+                build_pipeline: None,
+                asset_data: Box::new(Bytes(bytes)),
+            }],
+        })
+    }
+}
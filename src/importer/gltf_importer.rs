@@ -0,0 +1,511 @@
+use std::borrow::Cow;
+use std::collections::HashSet;
+use std::fmt;
+use std::io::Read;
+
+use distill_core::AssetRef;
+use distill_importer::{ImportedAsset, Importer, ImporterValue, SerdeObj};
+
+use super::image_importer::{decode_image, Image, ImageImporterOptions};
+use crate::handle::import_handle;
+use crate::prelude::*;
+use crate::util::{collect_load_deps, with_sibling_labels, LabeledUuidImporterState};
+
+/// Imports `.gltf` and `.glb` files into a [`Gltf`] asset and labelled sub-assets:
+///
+/// - `Mesh0`, `Mesh1`, ... of type [`GltfMesh`]
+/// - `Material0`, ... of type [`GltfMaterial`]
+/// - `Texture0`, ... of type [`Image`], for textures embedded in the file
+/// - `Scene0`, ... of type [`GltfScene`]
+///
+/// Sub-assets are loaded with `model.gltf#Mesh0@<type uuid>`, and their UUIDs are kept in the importer state.
+/// Textures referring to other files are load dependencies of their materials, those files are imported on
+/// their own, for example by the [`ImageImporter`](super::ImageImporter).
+///
+/// Buffers have to be embedded, either in the binary chunk of a `.glb` file or as `data:` URI.
+#[derive(TypeUuid)]
+#[uuid = "1f5a880d-9d13-4509-ac7d-6ac102c85bb3"]
+pub struct GltfImporter;
+
+#[derive(TypeUuid, Serialize, Deserialize, Clone, Debug)]
+#[uuid = "f0bdbab8-cf62-4f18-b234-e0af95078730"]
+pub struct Gltf {
+    pub scenes: Vec<Handle<GltfScene>>,
+    pub default_scene: Option<Handle<GltfScene>>,
+    pub meshes: Vec<Handle<GltfMesh>>,
+    pub materials: Vec<Handle<GltfMaterial>>,
+}
+
+#[derive(TypeUuid, Serialize, Deserialize, Clone, Debug)]
+#[uuid = "ec662468-13b7-4ff2-a31a-cb15132154fd"]
+pub struct GltfMesh {
+    pub primitives: Vec<GltfPrimitive>,
+}
+
+/// A triangle list, other primitive modes are skipped when importing.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct GltfPrimitive {
+    pub positions: Vec<[f32; 3]>,
+    pub normals: Option<Vec<[f32; 3]>>,
+    pub tex_coords: Option<Vec<[f32; 2]>>,
+    pub indices: Option<Vec<u32>>,
+    /// `None` for the default material.
+    pub material: Option<Handle<GltfMaterial>>,
+}
+
+/// A metallic-roughness material.
+#[derive(TypeUuid, Serialize, Deserialize, Clone, Debug)]
+#[uuid = "1d453ce2-5b5f-4995-bbd4-ec496391a983"]
+pub struct GltfMaterial {
+    pub base_color_factor: [f32; 4],
+    pub base_color_texture: Option<Handle<Image>>,
+    pub metallic_factor: f32,
+    pub roughness_factor: f32,
+    pub metallic_roughness_texture: Option<Handle<Image>>,
+    pub normal_texture: Option<Handle<Image>>,
+    pub occlusion_texture: Option<Handle<Image>>,
+    pub emissive_factor: [f32; 3],
+    pub emissive_texture: Option<Handle<Image>>,
+    pub alpha_mode: AlphaMode,
+    pub double_sided: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum AlphaMode {
+    Opaque,
+    /// Fully transparent below the cutoff, opaque above.
+    Mask(f32),
+    Blend,
+}
+
+#[derive(TypeUuid, Serialize, Deserialize, Clone, Debug)]
+#[uuid = "da5da3b8-9c28-40aa-ad69-95955c1faf0a"]
+pub struct GltfScene {
+    pub nodes: Vec<GltfNode>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct GltfNode {
+    pub name: Option<String>,
+    pub translation: [f32; 3],
+    /// A quaternion as `[x, y, z, w]`.
+    pub rotation: [f32; 4],
+    pub scale: [f32; 3],
+    pub mesh: Option<Handle<GltfMesh>>,
+    pub children: Vec<GltfNode>,
+}
+
+#[derive(Debug)]
+pub enum GltfImportError {
+    Gltf(gltf::Error),
+    Image(image::ImageError),
+    DataUri(base64::DecodeError),
+    /// A `data:` URI which isn't base64 encoded.
+    UnsupportedDataUri,
+    /// A buffer stored in another file.
+    ExternalBuffer(String),
+    /// A buffer refers to the binary chunk, but the file doesn't have one.
+    MissingBinaryChunk,
+    /// The buffer view with this index reaches past the end of its buffer.
+    BufferViewOutOfBounds(usize),
+    Handle(serde::de::value::Error),
+}
+
+impl fmt::Display for GltfImportError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GltfImportError::Gltf(e) => write!(f, "{}", e),
+            GltfImportError::Image(e) => write!(f, "failed to decode texture: {}", e),
+            GltfImportError::DataUri(e) => write!(f, "invalid data URI: {}", e),
+            GltfImportError::UnsupportedDataUri => write!(f, "data URIs have to be base64 encoded"),
+            GltfImportError::ExternalBuffer(uri) => write!(
+                f,
+                "external buffer `{}` is not supported, embed it or use a .glb file",
+                uri
+            ),
+            GltfImportError::MissingBinaryChunk => write!(f, "the file has no binary chunk"),
+            GltfImportError::BufferViewOutOfBounds(index) => {
+                write!(f, "buffer view {} is out of bounds of its buffer", index)
+            }
+            GltfImportError::Handle(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for GltfImportError {}
+
+impl From<gltf::Error> for GltfImportError {
+    fn from(e: gltf::Error) -> Self {
+        GltfImportError::Gltf(e)
+    }
+}
+impl From<image::ImageError> for GltfImportError {
+    fn from(e: image::ImageError) -> Self {
+        GltfImportError::Image(e)
+    }
+}
+impl From<serde::de::value::Error> for GltfImportError {
+    fn from(e: serde::de::value::Error) -> Self {
+        GltfImportError::Handle(e)
+    }
+}
+
+/// Labelled sub-assets together with their `load_deps`.
+type SubAssets = Vec<(String, Box<dyn SerdeObj>, Vec<AssetRef>)>;
+
+impl Importer for GltfImporter {
+    fn version_static() -> u32
+    where
+        Self: Sized,
+    {
+        1
+    }
+
+    fn version(&self) -> u32 {
+        Self::version_static()
+    }
+
+    type Options = ();
+    type State = LabeledUuidImporterState;
+
+    fn import(
+        &self,
+        _: &mut distill_importer::ImportOp,
+        source: &mut dyn Read,
+        _: &Self::Options,
+        state: &mut Self::State,
+    ) -> distill_importer::Result<ImporterValue> {
+        let mut bytes = Vec::new();
+        source.read_to_end(&mut bytes)?;
+
+        let ((gltf, load_deps), sub_assets) = with_sibling_labels(state, || import_gltf(&bytes))
+            .map_err(|e| Box::new(e) as Box<dyn std::error::Error + Send>)?;
+        state.retain_labels(|label| sub_assets.iter().any(|(other, ..)| other == label));
+
+        let mut assets = vec![ImportedAsset {
+            id: state.id(),
+            search_tags: vec![],
+            build_deps: vec![],
+            load_deps,
+            build_pipeline: None,
+            asset_data: Box::new(gltf),
+        }];
+        for (label, asset_data, load_deps) in sub_assets {
+            assets.push(ImportedAsset {
+                id: state.label_id(&label),
+                // `path#label@type` is resolved through the `name` tag
+                search_tags: vec![("name".to_string(), Some(label))],
+                build_deps: vec![],
+                load_deps,
+                build_pipeline: None,
+                asset_data,
+            });
+        }
+
+        Ok(ImporterValue { assets })
+    }
+}
+
+/// Has to run inside of [`with_sibling_labels`], which resolves the `#Label` handles between the sub-assets.
+fn import_gltf(bytes: &[u8]) -> Result<((Gltf, Vec<AssetRef>), SubAssets), GltfImportError> {
+    let gltf = gltf::Gltf::from_slice(bytes)?;
+    let buffers = gltf
+        .buffers()
+        .map(|buffer| match buffer.source() {
+            gltf::buffer::Source::Bin => {
+                gltf.blob.clone().ok_or(GltfImportError::MissingBinaryChunk)
+            }
+            gltf::buffer::Source::Uri(uri) => {
+                data_uri(uri)?.ok_or_else(|| GltfImportError::ExternalBuffer(uri.to_string()))
+            }
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let mut sub_assets = SubAssets::new();
+
+    // textures holding data instead of colors aren't sRGB encoded
+    let linear_textures: HashSet<usize> = gltf
+        .materials()
+        .flat_map(|material| {
+            [
+                material.normal_texture().map(|t| t.texture().index()),
+                material.occlusion_texture().map(|t| t.texture().index()),
+                material
+                    .pbr_metallic_roughness()
+                    .metallic_roughness_texture()
+                    .map(|t| t.texture().index()),
+            ]
+        })
+        .flatten()
+        .collect();
+
+    // what the handles of each texture are created from
+    let mut texture_refs = Vec::new();
+    for texture in gltf.textures() {
+        let data = match texture.source().source() {
+            gltf::image::Source::View { view, .. } => {
+                let data = buffers[view.buffer().index()]
+                    .get(view.offset()..view.offset() + view.length())
+                    .ok_or(GltfImportError::BufferViewOutOfBounds(view.index()))?;
+                Cow::Borrowed(data)
+            }
+            gltf::image::Source::Uri { uri, .. } => match data_uri(uri)? {
+                Some(data) => Cow::Owned(data),
+                None => {
+                    // relative to the glTF file, like `load_deps` paths
+                    texture_refs.push(uri.to_string());
+                    continue;
+                }
+            },
+        };
+        let options = ImageImporterOptions {
+            srgb: !linear_textures.contains(&texture.index()),
+            ..Default::default()
+        };
+        let image = decode_image(&data, &options)?;
+
+        let label = format!("Texture{}", texture.index());
+        texture_refs.push(format!("#{}", label));
+        sub_assets.push((label, Box::new(image), Vec::new()));
+    }
+
+    for material in gltf.materials() {
+        let index = match material.index() {
+            Some(index) => index,
+            None => continue,
+        };
+        let (result, load_deps) = collect_load_deps(|| import_material(&material, &texture_refs));
+        sub_assets.push((format!("Material{}", index), Box::new(result?), load_deps));
+    }
+
+    for mesh in gltf.meshes() {
+        let (result, load_deps) = collect_load_deps(|| import_mesh(&mesh, &buffers));
+        sub_assets.push((
+            format!("Mesh{}", mesh.index()),
+            Box::new(result?),
+            load_deps,
+        ));
+    }
+
+    for scene in gltf.scenes() {
+        let (result, load_deps) = collect_load_deps(|| {
+            scene
+                .nodes()
+                .map(import_node)
+                .collect::<Result<Vec<_>, _>>()
+        });
+        let scene_asset = GltfScene { nodes: result? };
+        sub_assets.push((
+            format!("Scene{}", scene.index()),
+            Box::new(scene_asset),
+            load_deps,
+        ));
+    }
+
+    let (result, load_deps) = collect_load_deps(|| -> Result<Gltf, GltfImportError> {
+        Ok(Gltf {
+            scenes: label_handles("Scene", gltf.scenes().len())?,
+            default_scene: gltf
+                .default_scene()
+                .map(|scene| import_handle(&format!("#Scene{}", scene.index())))
+                .transpose()?,
+            meshes: label_handles("Mesh", gltf.meshes().len())?,
+            materials: label_handles("Material", gltf.materials().len())?,
+        })
+    });
+
+    Ok(((result?, load_deps), sub_assets))
+}
+
+/// Handles to the sub-assets `{prefix}0` to `{prefix}{count - 1}`.
+fn label_handles<A: Asset>(
+    prefix: &str,
+    count: usize,
+) -> Result<Vec<Handle<A>>, serde::de::value::Error> {
+    (0..count)
+        .map(|i| import_handle(&format!("#{}{}", prefix, i)))
+        .collect()
+}
+
+/// Decodes a `data:` URI, returns `None` for other URIs.
+fn data_uri(uri: &str) -> Result<Option<Vec<u8>>, GltfImportError> {
+    let data = match uri.strip_prefix("data:") {
+        Some(data) => data,
+        None => return Ok(None),
+    };
+    match data.split_once(',') {
+        Some((header, data)) if header.ends_with(";base64") => base64::decode(data)
+            .map(Some)
+            .map_err(GltfImportError::DataUri),
+        _ => Err(GltfImportError::UnsupportedDataUri),
+    }
+}
+
+fn import_material(
+    material: &gltf::Material,
+    texture_refs: &[String],
+) -> Result<GltfMaterial, GltfImportError> {
+    let texture = |texture: gltf::Texture| import_handle(&texture_refs[texture.index()]);
+    let pbr = material.pbr_metallic_roughness();
+
+    Ok(GltfMaterial {
+        base_color_factor: pbr.base_color_factor(),
+        base_color_texture: pbr
+            .base_color_texture()
+            .map(|info| texture(info.texture()))
+            .transpose()?,
+        metallic_factor: pbr.metallic_factor(),
+        roughness_factor: pbr.roughness_factor(),
+        metallic_roughness_texture: pbr
+            .metallic_roughness_texture()
+            .map(|info| texture(info.texture()))
+            .transpose()?,
+        normal_texture: material
+            .normal_texture()
+            .map(|info| texture(info.texture()))
+            .transpose()?,
+        occlusion_texture: material
+            .occlusion_texture()
+            .map(|info| texture(info.texture()))
+            .transpose()?,
+        emissive_factor: material.emissive_factor(),
+        emissive_texture: material
+            .emissive_texture()
+            .map(|info| texture(info.texture()))
+            .transpose()?,
+        alpha_mode: match material.alpha_mode() {
+            gltf::material::AlphaMode::Opaque => AlphaMode::Opaque,
+            gltf::material::AlphaMode::Mask => {
+                AlphaMode::Mask(material.alpha_cutoff().unwrap_or(0.5))
+            }
+            gltf::material::AlphaMode::Blend => AlphaMode::Blend,
+        },
+        double_sided: material.double_sided(),
+    })
+}
+
+fn import_mesh(mesh: &gltf::Mesh, buffers: &[Vec<u8>]) -> Result<GltfMesh, GltfImportError> {
+    let mut primitives = Vec::new();
+    for primitive in mesh.primitives() {
+        if primitive.mode() != gltf::mesh::Mode::Triangles {
+            bevy_log::warn!(
+                "skipping primitive {} of mesh {} with unsupported mode {:?}",
+                primitive.index(),
+                mesh.index(),
+                primitive.mode()
+            );
+            continue;
+        }
+
+        let reader = primitive.reader(|buffer| buffers.get(buffer.index()).map(Vec::as_slice));
+        primitives.push(GltfPrimitive {
+            positions: reader
+                .read_positions()
+                .map(Iterator::collect)
+                .unwrap_or_default(),
+            normals: reader.read_normals().map(Iterator::collect),
+            tex_coords: reader
+                .read_tex_coords(0)
+                .map(|tex_coords| tex_coords.into_f32().collect()),
+            indices: reader
+                .read_indices()
+                .map(|indices| indices.into_u32().collect()),
+            material: primitive
+                .material()
+                .index()
+                .map(|index| import_handle(&format!("#Material{}", index)))
+                .transpose()?,
+        });
+    }
+    Ok(GltfMesh { primitives })
+}
+
+fn import_node(node: gltf::Node) -> Result<GltfNode, GltfImportError> {
+    let (translation, rotation, scale) = node.transform().decomposed();
+    Ok(GltfNode {
+        name: node.name().map(str::to_string),
+        translation,
+        rotation,
+        scale,
+        mesh: node
+            .mesh()
+            .map(|mesh| import_handle(&format!("#Mesh{}", mesh.index())))
+            .transpose()?,
+        children: node
+            .children()
+            .map(import_node)
+            .collect::<Result<Vec<_>, _>>()?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use distill_importer::ImportOp;
+
+    use super::*;
+    use crate::util::{deterministic_asset_uuid, with_deterministic_uuids};
+
+    fn import(source: &[u8]) -> distill_importer::Result<ImporterValue> {
+        with_deterministic_uuids(Path::new("triangle.gltf"), || {
+            GltfImporter.import(
+                &mut ImportOp::default(),
+                &mut &source[..],
+                &(),
+                &mut LabeledUuidImporterState::default(),
+            )
+        })
+    }
+
+    #[test]
+    fn imports_sample_with_embedded_texture() {
+        let source = std::fs::read_to_string("assets/triangle.gltf").unwrap();
+        let texture = base64::encode(std::fs::read("assets/base_color.png").unwrap());
+        let source = source.replace(
+            r#""uri": "base_color.png""#,
+            &format!(r#""uri": "data:image/png;base64,{}""#, texture),
+        );
+        let value = import(source.as_bytes()).unwrap();
+
+        let id = |label: &str| deterministic_asset_uuid(Path::new("triangle.gltf"), Some(label));
+        let asset = |label: &str| {
+            value
+                .assets
+                .iter()
+                .find(|asset| asset.search_tags == [("name".to_string(), Some(label.to_string()))])
+                .unwrap_or_else(|| panic!("no sub-asset {}", label))
+        };
+        assert_eq!(value.assets.len(), 5);
+        assert_eq!(
+            value.assets[0].id,
+            deterministic_asset_uuid(Path::new("triangle.gltf"), None)
+        );
+        for label in ["Texture0", "Material0", "Mesh0", "Scene0"] {
+            assert_eq!(asset(label).id, id(label));
+        }
+
+        assert_eq!(
+            asset("Material0").load_deps,
+            [AssetRef::Uuid(id("Texture0"))]
+        );
+        assert_eq!(asset("Mesh0").load_deps, [AssetRef::Uuid(id("Material0"))]);
+        assert_eq!(asset("Scene0").load_deps, [AssetRef::Uuid(id("Mesh0"))]);
+    }
+
+    #[test]
+    fn buffer_view_out_of_bounds_is_an_error() {
+        let source = r#"{
+            "asset": { "version": "2.0" },
+            "buffers": [{ "byteLength": 64, "uri": "data:application/octet-stream;base64,AAAA" }],
+            "bufferViews": [{ "buffer": 0, "byteOffset": 0, "byteLength": 64 }],
+            "images": [{ "bufferView": 0, "mimeType": "image/png" }],
+            "textures": [{ "source": 0 }]
+        }"#;
+        let error = import(source.as_bytes()).err().unwrap().to_string();
+        assert!(
+            error.contains("buffer view 0 is out of bounds"),
+            "{}",
+            error
+        );
+    }
+}
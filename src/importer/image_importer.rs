@@ -0,0 +1,183 @@
+use std::io::Read;
+
+use distill_importer::{ImportedAsset, Importer, ImporterValue};
+
+use crate::prelude::*;
+use crate::util::AssetUuidImporterState;
+
+/// Imports PNG, JPEG and BMP images as [`Image`]s with 8 bit RGBA pixels.
+#[derive(TypeUuid)]
+#[uuid = "0dc68688-7397-4b8b-82bb-c77e2cb272ea"]
+pub struct ImageImporter;
+
+/// An image with 8 bit RGBA pixels, stored row by row.
+#[derive(TypeUuid, Serialize, Deserialize, Clone, Debug)]
+#[uuid = "a4e491f6-a627-4d8f-8255-7ba2d2b33b62"]
+pub struct Image {
+    pub width: u32,
+    pub height: u32,
+    pub format: ImageFormat,
+    /// Whether the color channels are already multiplied with the alpha channel.
+    pub premultiplied_alpha: bool,
+    pub data: Vec<u8>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ImageFormat {
+    /// The color channels are linear.
+    Rgba8Unorm,
+    /// The color channels are sRGB encoded, alpha is linear.
+    Rgba8UnormSrgb,
+}
+
+/// Options of the [`ImageImporter`], set in the `importer_options` of a `.meta` file.
+#[derive(TypeUuid, Serialize, Deserialize, Clone, Debug)]
+#[serde(default)]
+#[uuid = "2eb8b5a7-07e4-4743-bb8a-fc3d7b521af1"]
+pub struct ImageImporterOptions {
+    /// Whether the pixels are sRGB encoded, like colors usually are, or linear,
+    /// like normal maps and other data. Only the [`ImageFormat`] changes, not the pixels.
+    pub srgb: bool,
+    /// Multiply the color channels with the alpha channel.
+    pub premultiply_alpha: bool,
+}
+
+impl Default for ImageImporterOptions {
+    fn default() -> Self {
+        ImageImporterOptions {
+            srgb: true,
+            premultiply_alpha: false,
+        }
+    }
+}
+
+impl Image {
+    pub fn pixel(&self, x: u32, y: u32) -> [u8; 4] {
+        let i = (y as usize * self.width as usize + x as usize) * 4;
+        [
+            self.data[i],
+            self.data[i + 1],
+            self.data[i + 2],
+            self.data[i + 3],
+        ]
+    }
+}
+
+/// Decodes a PNG, JPEG or BMP image, detecting the format from the contents.
+pub(crate) fn decode_image(
+    bytes: &[u8],
+    options: &ImageImporterOptions,
+) -> Result<Image, image::ImageError> {
+    let image = image::load_from_memory(bytes)?.to_rgba8();
+    let (width, height) = image.dimensions();
+    let mut data = image.into_raw();
+    if options.premultiply_alpha {
+        premultiply_alpha(&mut data);
+    }
+
+    Ok(Image {
+        width,
+        height,
+        format: if options.srgb {
+            ImageFormat::Rgba8UnormSrgb
+        } else {
+            ImageFormat::Rgba8Unorm
+        },
+        premultiplied_alpha: options.premultiply_alpha,
+        data,
+    })
+}
+
+fn premultiply_alpha(data: &mut [u8]) {
+    for pixel in data.chunks_exact_mut(4) {
+        let alpha = pixel[3] as u16;
+        for channel in &mut pixel[..3] {
+            *channel = ((*channel as u16 * alpha + 127) / 255) as u8;
+        }
+    }
+}
+
+impl Importer for ImageImporter {
+    fn version_static() -> u32
+    where
+        Self: Sized,
+    {
+        1
+    }
+
+    fn version(&self) -> u32 {
+        Self::version_static()
+    }
+
+    type Options = ImageImporterOptions;
+    type State = AssetUuidImporterState;
+
+    fn import(
+        &self,
+        _: &mut distill_importer::ImportOp,
+        source: &mut dyn Read,
+        options: &Self::Options,
+        state: &mut Self::State,
+    ) -> distill_importer::Result<ImporterValue> {
+        let mut bytes = Vec::new();
+        source.read_to_end(&mut bytes)?;
+
+        // the format is detected from the contents, so the file extension doesn't matter
+        let image = decode_image(&bytes, options)
+            .map_err(|e| Box::new(e) as Box<dyn std::error::Error + Send>)?;
+
+        Ok(ImporterValue {
+            assets: vec![ImportedAsset {
+                id: state.id(),
+                search_tags: vec![],
+                build_deps: vec![],
+                load_deps: vec![],
+                build_pipeline: None,
+                asset_data: Box::new(image),
+            }],
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn format_follows_srgb_option() {
+        let bytes = std::fs::read("assets/base_color.png").unwrap();
+        for (srgb, format) in [
+            (true, ImageFormat::Rgba8UnormSrgb),
+            (false, ImageFormat::Rgba8Unorm),
+        ] {
+            let options = ImageImporterOptions {
+                srgb,
+                ..Default::default()
+            };
+            let image = decode_image(&bytes, &options).unwrap();
+            assert_eq!(image.format, format);
+            assert_eq!((image.width, image.height), (128, 128));
+            // only the format changes, not the pixels
+            assert_eq!(image.pixel(0, 0), [11, 93, 99, 255]);
+        }
+    }
+
+    #[test]
+    fn premultiplies_alpha() {
+        let pixels = [200, 100, 50, 128, 255, 255, 255, 0, 10, 20, 30, 255];
+        let mut bytes = Vec::new();
+        image::png::PngEncoder::new(&mut bytes)
+            .encode(&pixels, 3, 1, image::ColorType::Rgba8)
+            .unwrap();
+
+        let options = ImageImporterOptions {
+            premultiply_alpha: true,
+            ..Default::default()
+        };
+        let image = decode_image(&bytes, &options).unwrap();
+        assert!(image.premultiplied_alpha);
+        assert_eq!(image.pixel(0, 0), [100, 50, 25, 128]);
+        assert_eq!(image.pixel(1, 0), [0, 0, 0, 0]);
+        assert_eq!(image.pixel(2, 0), [10, 20, 30, 255]);
+    }
+}
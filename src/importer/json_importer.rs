@@ -0,0 +1,130 @@
+use std::marker::PhantomData;
+
+use bevy_reflect::Uuid;
+use distill_importer::{ImportedAsset, Importer, ImporterValue};
+
+use crate::prelude::*;
+use crate::util::{collect_load_deps, importer_uuid, AssetUuidImporterState};
+
+pub struct JsonImporter<A: Asset + Serialize>(PhantomData<A>);
+#[cfg(feature = "json-importer")]
+impl<A: Asset + Serialize> TypeUuid for JsonImporter<A> {
+    /// Derived from the asset type, so each `JsonImporter<A>` is a different importer.
+    const TYPE_UUID: Uuid = Uuid::from_bytes(importer_uuid(
+        &[
+            253, 66, 82, 158, 150, 168, 72, 59, 136, 103, 165, 213, 162, 234, 177, 32,
+        ],
+        A::TYPE_UUID.as_bytes(),
+    ));
+}
+
+#[cfg(feature = "json-importer")]
+impl<A: Asset + Serialize> JsonImporter<A> {
+    pub fn new() -> Self {
+        JsonImporter(PhantomData)
+    }
+}
+#[cfg(feature = "json-importer")]
+impl<A: Asset + Serialize + for<'de> Deserialize<'de>> Importer for JsonImporter<A> {
+    fn version_static() -> u32
+    where
+        Self: Sized,
+    {
+        // 2: fills `load_deps` with the handles in the source
+        2
+    }
+
+    fn version(&self) -> u32 {
+        Self::version_static()
+    }
+
+    type Options = ();
+    type State = AssetUuidImporterState;
+
+    fn import(
+        &self,
+        _: &mut distill_importer::ImportOp,
+        source: &mut dyn std::io::Read,
+        _: &Self::Options,
+        state: &mut Self::State,
+    ) -> distill_importer::Result<ImporterValue> {
+        // the error message contains the line and column
+        let (data, load_deps) = collect_load_deps(|| serde_json::from_reader(source));
+        let data: A = data.map_err(|e| Box::new(e) as Box<dyn std::error::Error + Send>)?;
+
+        let id = state.id();
+
+        Ok(ImporterValue {
+            assets: vec![ImportedAsset {
+                id,
+                search_tags: vec![],
+                build_deps: vec![],
+                load_deps,
+                build_pipeline: None,
+                asset_data: Box::new(data),
+            }],
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+
+    use distill_core::{AssetRef, AssetUuid};
+
+    use super::*;
+    use crate::importer::text_importer::Text;
+    use crate::io::import::import_source;
+
+    #[derive(Serialize, Deserialize, TypeUuid)]
+    #[uuid = "0d7e3a52-6c1b-4f08-b9a4-73e2c5d81f6a"]
+    struct Dialogue {
+        speaker: String,
+        lines: Vec<String>,
+        voice_over: Handle<Text>,
+    }
+
+    /// The fields of [`Dialogue`] before its handle, which can't be deserialized outside of an import.
+    #[derive(Deserialize)]
+    struct DialogueText {
+        speaker: String,
+        lines: Vec<String>,
+    }
+
+    #[test]
+    fn imports_dialogue_with_handle() {
+        // handles are only created inside of the serde context of an import
+        let path = Path::new("assets/config/dialogue.json");
+        let mut source = import_source(&JsonImporter::<Dialogue>::new(), path, None, None).unwrap();
+        assert_eq!(source.assets().len(), 1);
+        assert_eq!(
+            source.assets()[0].load_deps,
+            [AssetRef::Path(PathBuf::from("lorem_ipsum.txt"))]
+        );
+
+        // the handle can only be serialized once its path is resolved
+        let lorem_ipsum = AssetUuid(
+            *uuid::Uuid::parse_str("1d44085f-d8c4-4a2f-a4c1-c26c3e2902f1")
+                .unwrap()
+                .as_bytes(),
+        );
+        let artifacts = source
+            .serialize(Path::new("config/dialogue.json"), |path| {
+                (path == Path::new("lorem_ipsum.txt")).then(|| lorem_ipsum)
+            })
+            .unwrap();
+        assert_eq!(
+            uuid::Uuid::from_bytes(artifacts[0].id().0).to_string(),
+            "8f4c2a61-3b7e-4d95-a0c8-5e21d9f6b473"
+        );
+        assert_eq!(
+            artifacts[0].artifact().type_id.0,
+            *Dialogue::TYPE_UUID.as_bytes()
+        );
+        // bincode ignores the trailing bytes of the handle
+        let dialogue: DialogueText = bincode::deserialize(&artifacts[0].data).unwrap();
+        assert_eq!(dialogue.speaker, "archer");
+        assert_eq!(dialogue.lines, ["Ready.", "On my way."]);
+    }
+}
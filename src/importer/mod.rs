@@ -1,7 +1,58 @@
+mod bytes_importer;
+#[cfg(feature = "gltf-importer")]
+mod gltf_importer;
+#[cfg(feature = "image-importer")]
+mod image_importer;
+#[cfg(feature = "json-importer")]
+mod json_importer;
 #[cfg(feature = "ron-importer")]
 mod ron_importer;
+mod shader_importer;
+mod simple_importer;
 pub mod text_importer;
+#[cfg(feature = "toml-importer")]
+mod toml_importer;
+#[cfg(feature = "yaml-importer")]
+mod yaml_importer;
 
+use distill_importer::BoxedImporter;
+
+pub use bytes_importer::BytesImporter;
+#[cfg(feature = "gltf-importer")]
+pub use gltf_importer::{
+    AlphaMode, Gltf, GltfImportError, GltfImporter, GltfMaterial, GltfMesh, GltfNode,
+    GltfPrimitive, GltfScene,
+};
+#[cfg(feature = "image-importer")]
+pub use image_importer::{Image, ImageFormat, ImageImporter, ImageImporterOptions};
+#[cfg(feature = "json-importer")]
+pub use json_importer::JsonImporter;
 #[cfg(feature = "ron-importer")]
-pub use ron_importer::RonImporter;
-pub use text_importer::TextImporter;
+pub use ron_importer::{RonImporter, RonImporterOptions, UnknownFieldsError, UnknownLabelError};
+pub use shader_importer::{ShaderInclude, ShaderIncludeError, ShaderSource, ShaderSourceImporter};
+pub use simple_importer::{ImportContext, Simple, SimpleImporter};
+pub use text_importer::{
+    Newlines, TextEncoding, TextImportError, TextImporter, TextImporterOptions,
+};
+#[cfg(feature = "toml-importer")]
+pub use toml_importer::TomlImporter;
+#[cfg(feature = "yaml-importer")]
+pub use yaml_importer::YamlImporter;
+
+/// The importers of the asset types of this crate with the extensions they handle,
+/// for tools that import assets without an app, like the `bevy-asset-pack` binary.
+pub fn builtin_importers() -> Vec<(&'static [&'static str], Box<dyn BoxedImporter>)> {
+    #[allow(unused_mut)]
+    let mut importers: Vec<(&'static [&'static str], Box<dyn BoxedImporter>)> = vec![
+        (&["txt"], Box::new(TextImporter)),
+        (
+            &["wgsl", "glsl", "vert", "frag"],
+            Box::new(ShaderSourceImporter),
+        ),
+    ];
+    #[cfg(feature = "image-importer")]
+    importers.push((&["png", "jpg", "jpeg", "bmp"], Box::new(ImageImporter)));
+    #[cfg(feature = "gltf-importer")]
+    importers.push((&["gltf", "glb"], Box::new(GltfImporter)));
+    importers
+}
@@ -1,24 +1,121 @@
+use std::fmt;
 use std::marker::PhantomData;
 
 use bevy_reflect::Uuid;
-use distill_importer::{ImportedAsset, Importer, ImporterValue};
+use bevy_utils::HashMap;
+use distill_core::AssetRef;
+use distill_importer::{ImportedAsset, Importer, ImporterValue, SerdeObj};
+use ron::extensions::Extensions;
+use serde::de::{self, DeserializeSeed, IgnoredAny, MapAccess, Visitor};
 
 use crate::prelude::*;
-use crate::util::AssetUuidImporterState;
+use crate::util::{
+    collect_load_deps, importer_uuid, with_sibling_labels, LabeledUuidImporterState,
+};
 
-pub struct RonImporter<A: Asset + Serialize>(PhantomData<A>);
+/// Options of the [`RonImporter`], set in the `importer_options` of a `.meta` file.
+///
+/// ```ron
+/// importer_options: (
+///     implicit_some: true,
+///     deny_unknown_fields: true,
+/// ),
+/// ```
+#[derive(TypeUuid, Serialize, Deserialize, Clone, Debug, Default)]
+#[serde(default)]
+#[uuid = "e2285a9b-eca9-4c3b-bd1c-32d3155b26d1"]
+pub struct RonImporterOptions {
+    /// Enables the `implicit_some` extension, so optional values can be written without `Some(..)`.
+    pub implicit_some: bool,
+    /// Enables the `unwrap_newtypes` extension, so newtype structs can be written without parentheses.
+    pub unwrap_newtypes: bool,
+    /// Fail the import on fields the asset doesn't have, instead of ignoring them with a warning.
+    pub deny_unknown_fields: bool,
+}
+
+#[derive(Debug)]
+pub struct UnknownFieldsError(Vec<String>);
+
+impl fmt::Display for UnknownFieldsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "unknown fields: {}", self.0.join(", "))
+    }
+}
+
+impl std::error::Error for UnknownFieldsError {}
+
+/// A `#label` handle points at a sub-asset the file doesn't contain.
+#[derive(Debug)]
+pub struct UnknownLabelError(String);
+
+impl fmt::Display for UnknownLabelError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "no sub-asset with label `{}`", self.0)
+    }
+}
+
+impl std::error::Error for UnknownLabelError {}
+
+type DeserializeSubAsset =
+    fn(&mut dyn erased_serde::Deserializer<'_>) -> Result<Box<dyn SerdeObj>, erased_serde::Error>;
+
+/// Imports RON files containing an asset of type `A`.
+///
+/// With [`with_sub_asset`](Self::with_sub_asset), files can additionally declare labelled sub-assets,
+/// which are loaded with `file.ron#Label@<type uuid>` and can be referenced by `Handle` fields of the
+/// same file as `"#Label"`:
+///
+/// ```ron
+/// (
+///     asset: (
+///         entities: ["#Greeting"],
+///     ),
+///     sub_assets: {
+///         "Greeting": (type: "TheAsset", value: "hello"),
+///     },
+/// )
+/// ```
+pub struct RonImporter<A: Asset + Serialize> {
+    sub_asset_types: HashMap<&'static str, DeserializeSubAsset>,
+    marker: PhantomData<A>,
+}
 #[cfg(feature = "ron-importer")]
 impl<A: Asset + Serialize> TypeUuid for RonImporter<A> {
-    const TYPE_UUID: Uuid = Uuid::from_bytes([
-        247, 147, 34, 237, 214, 174, 75, 180, 169, 124, 10, 136, 213, 57, 10, 161,
-    ]);
+    /// Derived from the asset type, so each `RonImporter<A>` is a different importer.
+    const TYPE_UUID: Uuid = Uuid::from_bytes(importer_uuid(
+        &[
+            247, 147, 34, 237, 214, 174, 75, 180, 169, 124, 10, 136, 213, 57, 10, 161,
+        ],
+        A::TYPE_UUID.as_bytes(),
+    ));
 }
 
 #[cfg(feature = "ron-importer")]
 impl<A: Asset + Serialize> RonImporter<A> {
     pub fn new() -> Self {
-        RonImporter(PhantomData)
+        RonImporter {
+            sub_asset_types: HashMap::default(),
+            marker: PhantomData,
+        }
     }
+
+    /// Allows sub-assets of type `T`, written as `(type: "<name>", value: ..)` in the `sub_assets` of a file.
+    ///
+    /// Once a sub-asset type is registered, files have to be written as `(asset: .., sub_assets: {..})`.
+    pub fn with_sub_asset<T: Asset + Serialize + for<'de> Deserialize<'de>>(
+        mut self,
+        name: &'static str,
+    ) -> Self {
+        self.sub_asset_types
+            .insert(name, deserialize_sub_asset::<T>);
+        self
+    }
+}
+
+fn deserialize_sub_asset<T: Asset + Serialize + for<'de> Deserialize<'de>>(
+    deserializer: &mut dyn erased_serde::Deserializer<'_>,
+) -> Result<Box<dyn SerdeObj>, erased_serde::Error> {
+    erased_serde::deserialize::<T>(deserializer).map(|asset| Box::new(asset) as Box<dyn SerdeObj>)
 }
 #[cfg(feature = "ron-importer")]
 impl<A: Asset + Serialize + for<'de> Deserialize<'de>> Importer for RonImporter<A> {
@@ -26,37 +123,293 @@ impl<A: Asset + Serialize + for<'de> Deserialize<'de>> Importer for RonImporter<
     where
         Self: Sized,
     {
-        1
+        // 2: the importer UUID depends on the asset type, which makes the daemon import sources again.
+        //    `.meta` files only hold the options and state, so the assets keep their UUIDs
+        // 3: added `RonImporterOptions`
+        // 4: labelled sub-assets, stored in `LabeledUuidImporterState`
+        // 5: fills `load_deps` with the handles in the source
+        5
     }
 
     fn version(&self) -> u32 {
-        1
+        Self::version_static()
     }
 
-    type Options = ();
-    type State = AssetUuidImporterState;
+    type Options = RonImporterOptions;
+    type State = LabeledUuidImporterState;
 
     fn import(
         &self,
         _: &mut distill_importer::ImportOp,
         source: &mut dyn std::io::Read,
-        _: &Self::Options,
+        options: &Self::Options,
         state: &mut Self::State,
     ) -> distill_importer::Result<ImporterValue> {
-        let data: A = ron::de::from_reader(source)
+        let mut string = String::new();
+        source.read_to_string(&mut string)?;
+
+        let mut extensions = Extensions::empty();
+        extensions.set(Extensions::IMPLICIT_SOME, options.implicit_some);
+        extensions.set(Extensions::UNWRAP_NEWTYPES, options.unwrap_newtypes);
+        let mut deserializer = ron::Deserializer::from_str_with_options(
+            &string,
+            ron::Options::default().with_default_extension(extensions),
+        )
+        .map_err(|e| Box::new(e) as Box<dyn std::error::Error + Send>)?;
+
+        let mut unknown_fields = Vec::new();
+        let mut on_unknown_field =
+            |path: serde_ignored::Path| unknown_fields.push(path.to_string());
+        // handles inside of sub-assets are collected separately, see `ErasedSeed`
+        let (result, load_deps) = collect_load_deps(|| {
+            with_sibling_labels(state, || {
+                let ignored =
+                    serde_ignored::Deserializer::new(&mut deserializer, &mut on_unknown_field);
+                if self.sub_asset_types.is_empty() {
+                    A::deserialize(ignored).map(|data| (data, Vec::new()))
+                } else {
+                    DocumentSeed {
+                        sub_asset_types: &self.sub_asset_types,
+                        marker: PhantomData,
+                    }
+                    .deserialize(ignored)
+                }
+            })
+        });
+        let (data, sub_assets) = result
+            .and_then(|data| deserializer.end().map(|_| data))
             .map_err(|e| Box::new(e) as Box<dyn std::error::Error + Send>)?;
 
-        let id = state.id();
+        if !unknown_fields.is_empty() {
+            if options.deny_unknown_fields {
+                let error = Box::new(UnknownFieldsError(unknown_fields));
+                return Err((error as Box<dyn std::error::Error + Send>).into());
+            }
+            bevy_log::warn!(
+                "ignoring unknown fields of {}: {}",
+                std::any::type_name::<A>(),
+                unknown_fields.join(", ")
+            );
+        }
+
+        let is_sub_asset = |label: &str| sub_assets.iter().any(|(other, ..)| other == label);
+        if let Some(label) = state.referenced_labels().find(|label| !is_sub_asset(label)) {
+            let error = Box::new(UnknownLabelError(label.to_string()));
+            return Err((error as Box<dyn std::error::Error + Send>).into());
+        }
+        state.retain_labels(is_sub_asset);
 
-        Ok(ImporterValue {
-            assets: vec![ImportedAsset {
-                id,
-                search_tags: vec![],
+        let mut assets = vec![ImportedAsset {
+            id: state.id(),
+            search_tags: vec![],
+            build_deps: vec![],
+            load_deps,
+            build_pipeline: None,
+            asset_data: Box::new(data),
+        }];
+        for (label, asset_data, load_deps) in sub_assets {
+            assets.push(ImportedAsset {
+                id: state.label_id(&label),
+                // `path#label@type` is resolved through the `name` tag
+                search_tags: vec![("name".to_string(), Some(label))],
                 build_deps: vec![],
-                load_deps: vec![],
+                load_deps,
                 build_pipeline: None,
-                asset_data: Box::new(data),
-            }],
-        })
+                asset_data,
+            });
+        }
+
+        Ok(ImporterValue { assets })
+    }
+}
+
+type SubAssets = Vec<(String, Box<dyn SerdeObj>, Vec<AssetRef>)>;
+
+/// A file with sub-assets: `(asset: A, sub_assets: { label: (type: name, value: ..) })`.
+struct DocumentSeed<'a, A> {
+    sub_asset_types: &'a HashMap<&'static str, DeserializeSubAsset>,
+    marker: PhantomData<A>,
+}
+
+const DOCUMENT_FIELDS: &[&str] = &["asset", "sub_assets"];
+
+impl<'de, 'a, A: Deserialize<'de>> DeserializeSeed<'de> for DocumentSeed<'a, A> {
+    type Value = (A, SubAssets);
+
+    fn deserialize<D: de::Deserializer<'de>>(
+        self,
+        deserializer: D,
+    ) -> Result<Self::Value, D::Error> {
+        deserializer.deserialize_struct("Document", DOCUMENT_FIELDS, self)
+    }
+}
+
+impl<'de, 'a, A: Deserialize<'de>> Visitor<'de> for DocumentSeed<'a, A> {
+    type Value = (A, SubAssets);
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a struct with `asset` and `sub_assets`")
+    }
+
+    fn visit_map<M: MapAccess<'de>>(self, mut map: M) -> Result<Self::Value, M::Error> {
+        let mut asset = None;
+        let mut sub_assets = None;
+        while let Some(key) = map.next_key::<String>()? {
+            match key.as_str() {
+                "asset" if asset.is_some() => return Err(de::Error::duplicate_field("asset")),
+                "asset" => asset = Some(map.next_value()?),
+                "sub_assets" if sub_assets.is_some() => {
+                    return Err(de::Error::duplicate_field("sub_assets"))
+                }
+                "sub_assets" => {
+                    sub_assets = Some(map.next_value_seed(SubAssetsSeed(self.sub_asset_types))?)
+                }
+                _ => return Err(de::Error::unknown_field(&key, DOCUMENT_FIELDS)),
+            }
+        }
+        let asset = asset.ok_or_else(|| de::Error::missing_field("asset"))?;
+        Ok((asset, sub_assets.unwrap_or_default()))
+    }
+}
+
+struct SubAssetsSeed<'a>(&'a HashMap<&'static str, DeserializeSubAsset>);
+
+impl<'de, 'a> DeserializeSeed<'de> for SubAssetsSeed<'a> {
+    type Value = SubAssets;
+
+    fn deserialize<D: de::Deserializer<'de>>(
+        self,
+        deserializer: D,
+    ) -> Result<Self::Value, D::Error> {
+        deserializer.deserialize_map(self)
+    }
+}
+
+impl<'de, 'a> Visitor<'de> for SubAssetsSeed<'a> {
+    type Value = SubAssets;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a map from labels to sub-assets")
+    }
+
+    fn visit_map<M: MapAccess<'de>>(self, mut map: M) -> Result<Self::Value, M::Error> {
+        let mut sub_assets: SubAssets = Vec::new();
+        while let Some(label) = map.next_key::<String>()? {
+            if sub_assets.iter().any(|(other, ..)| *other == label) {
+                return Err(de::Error::custom(format!(
+                    "duplicate sub-asset label `{}`",
+                    label
+                )));
+            }
+            let (asset, load_deps) = map.next_value_seed(SubAssetSeed(self.0))?;
+            sub_assets.push((label, asset, load_deps));
+        }
+        Ok(sub_assets)
+    }
+}
+
+/// A single sub-asset: `(type: name, value: ..)`. The type has to come first.
+struct SubAssetSeed<'a>(&'a HashMap<&'static str, DeserializeSubAsset>);
+
+impl<'de, 'a> DeserializeSeed<'de> for SubAssetSeed<'a> {
+    type Value = (Box<dyn SerdeObj>, Vec<AssetRef>);
+
+    fn deserialize<D: de::Deserializer<'de>>(
+        self,
+        deserializer: D,
+    ) -> Result<Self::Value, D::Error> {
+        deserializer.deserialize_struct("SubAsset", &["type", "value"], self)
+    }
+}
+
+impl<'de, 'a> Visitor<'de> for SubAssetSeed<'a> {
+    type Value = (Box<dyn SerdeObj>, Vec<AssetRef>);
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a sub-asset `(type: .., value: ..)`")
+    }
+
+    fn visit_map<M: MapAccess<'de>>(self, mut map: M) -> Result<Self::Value, M::Error> {
+        if map.next_key::<String>()?.as_deref() != Some("type") {
+            return Err(de::Error::custom(
+                "`type` has to be the first field of a sub-asset",
+            ));
+        }
+        let type_name: String = map.next_value()?;
+        let deserialize = *self
+            .0
+            .get(type_name.as_str())
+            .ok_or_else(|| de::Error::custom(format!("unknown sub-asset type `{}`", type_name)))?;
+
+        if map.next_key::<String>()?.as_deref() != Some("value") {
+            return Err(de::Error::missing_field("value"));
+        }
+        let asset = map.next_value_seed(ErasedSeed(deserialize))?;
+
+        if map.next_key::<IgnoredAny>()?.is_some() {
+            return Err(de::Error::custom(
+                "a sub-asset only has the fields `type` and `value`",
+            ));
+        }
+        Ok(asset)
+    }
+}
+
+/// Deserializes a sub-asset of a registered type and collects its own `load_deps`.
+struct ErasedSeed(DeserializeSubAsset);
+
+impl<'de> DeserializeSeed<'de> for ErasedSeed {
+    type Value = (Box<dyn SerdeObj>, Vec<AssetRef>);
+
+    fn deserialize<D: de::Deserializer<'de>>(
+        self,
+        deserializer: D,
+    ) -> Result<Self::Value, D::Error> {
+        let mut erased = <dyn erased_serde::Deserializer>::erase(deserializer);
+        let (asset, load_deps) = collect_load_deps(|| (self.0)(&mut erased));
+        asset
+            .map(|asset| (asset, load_deps))
+            .map_err(de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+    use crate::io::import::import_source;
+
+    #[derive(TypeUuid, Serialize, Deserialize)]
+    #[uuid = "fab4249b-f95d-411d-a017-7549df090a4f"]
+    struct CustomAsset {
+        cool_string: String,
+    }
+
+    #[test]
+    fn keeps_asset_uuid_of_meta_file_from_version_1() {
+        let dir = std::env::temp_dir().join(format!("bevy_asset_ron_meta_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("custom_asset.casset");
+        std::fs::copy("assets/custom_asset.casset", &path).unwrap();
+        // written by version 1, whose UUID was the same for every asset type
+        std::fs::write(
+            dir.join("custom_asset.casset.meta"),
+            r#"(
+                version: 2,
+                importer_options: (),
+                importer_state: (Some("d1159d76-813b-4900-a344-c2568070abc0")),
+            )"#,
+        )
+        .unwrap();
+
+        let source = import_source(&RonImporter::<CustomAsset>::new(), &path, None, None).unwrap();
+        let ids: Vec<_> = source
+            .asset_ids()
+            .map(|id| uuid::Uuid::from_bytes(id.0).to_string())
+            .collect();
+        assert_eq!(ids, ["d1159d76-813b-4900-a344-c2568070abc0"]);
+
+        std::fs::remove_dir_all(&dir).unwrap();
     }
 }
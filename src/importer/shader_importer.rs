@@ -0,0 +1,250 @@
+use std::collections::HashSet;
+use std::fmt;
+use std::path::PathBuf;
+
+use distill_core::AssetRef;
+use distill_importer::{ImportedAsset, Importer, ImporterValue};
+use distill_loader::handle::AssetHandle;
+
+use crate::handle::import_handle;
+use crate::prelude::*;
+use crate::util::{collect_load_deps, AssetUuidImporterState};
+use crate::WeakHandle;
+
+/// Imports WGSL or GLSL sources with `#include "path"` or `#include <path>` directives.
+///
+/// Included files are build dependencies, so the asset daemon imports a shader again when one
+/// of its includes changes. They are also loaded together with the shader, to be inserted
+/// with [`ShaderSource::resolve`]. Paths are relative to the including file.
+#[derive(TypeUuid)]
+#[uuid = "1c61231e-3430-4261-8889-37bf610eaf13"]
+pub struct ShaderSourceImporter;
+
+#[derive(TypeUuid, Serialize, Deserialize, Clone, Debug)]
+#[uuid = "581f6a5b-7d0c-40fd-bd7f-831a3eebf7d7"]
+pub struct ShaderSource {
+    /// The source as it is in the file, including the include directives.
+    pub source: String,
+    pub includes: Vec<ShaderInclude>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ShaderInclude {
+    /// The index of the line with the include directive.
+    pub line: usize,
+    pub path: String,
+    pub shader: Handle<ShaderSource>,
+}
+
+#[derive(Debug)]
+pub enum ShaderIncludeError {
+    /// The shader to resolve isn't loaded (yet).
+    ShaderNotLoaded,
+    /// The included shader isn't loaded (yet).
+    NotLoaded(String),
+}
+
+impl fmt::Display for ShaderIncludeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ShaderIncludeError::ShaderNotLoaded => write!(f, "shader is not loaded"),
+            ShaderIncludeError::NotLoaded(path) => {
+                write!(f, "included shader `{}` is not loaded", path)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ShaderIncludeError {}
+
+impl ShaderSource {
+    /// Returns the source of the shader `handle` with the include directives replaced by the included sources.
+    ///
+    /// Like with `#pragma once`, a file included multiple times is only inserted the first time,
+    /// which also ends include cycles. The shader itself counts as included.
+    pub fn resolve<T: AssetHandle>(
+        handle: &T,
+        shaders: &Assets<ShaderSource>,
+    ) -> Result<String, ShaderIncludeError> {
+        let handle = shaders
+            .resolve(handle)
+            .ok_or(ShaderIncludeError::ShaderNotLoaded)?;
+        let shader = shaders
+            .get(&handle)
+            .ok_or(ShaderIncludeError::ShaderNotLoaded)?;
+
+        let mut resolved = String::with_capacity(shader.source.len());
+        let mut included = HashSet::new();
+        included.insert(handle);
+        shader.resolve_into(shaders, &mut resolved, &mut included)?;
+        Ok(resolved)
+    }
+
+    fn resolve_into(
+        &self,
+        shaders: &Assets<ShaderSource>,
+        resolved: &mut String,
+        included: &mut HashSet<WeakHandle<ShaderSource>>,
+    ) -> Result<(), ShaderIncludeError> {
+        let mut includes = self.includes.iter().peekable();
+        for (i, line) in self.source.lines().enumerate() {
+            let include = match includes.next_if(|include| include.line == i) {
+                Some(include) => include,
+                None => {
+                    resolved.push_str(line);
+                    resolved.push('\n');
+                    continue;
+                }
+            };
+
+            let not_loaded = || ShaderIncludeError::NotLoaded(include.path.clone());
+            let handle = shaders.resolve(&include.shader).ok_or_else(not_loaded)?;
+            if included.insert(handle) {
+                let shader = shaders.get(&handle).ok_or_else(not_loaded)?;
+                shader.resolve_into(shaders, resolved, included)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Returns the path of an include directive.
+fn parse_include(line: &str) -> Option<&str> {
+    let rest = line.trim_start().strip_prefix("#include")?.trim();
+    rest.strip_prefix('"')
+        .and_then(|rest| rest.strip_suffix('"'))
+        .or_else(|| {
+            rest.strip_prefix('<')
+                .and_then(|rest| rest.strip_suffix('>'))
+        })
+}
+
+impl Importer for ShaderSourceImporter {
+    fn version_static() -> u32
+    where
+        Self: Sized,
+    {
+        1
+    }
+
+    fn version(&self) -> u32 {
+        Self::version_static()
+    }
+
+    type Options = ();
+    type State = AssetUuidImporterState;
+
+    fn import(
+        &self,
+        _: &mut distill_importer::ImportOp,
+        source: &mut dyn std::io::Read,
+        _: &Self::Options,
+        state: &mut Self::State,
+    ) -> distill_importer::Result<ImporterValue> {
+        let mut source_text = String::new();
+        source.read_to_string(&mut source_text)?;
+
+        let (includes, load_deps) = collect_load_deps(|| {
+            source_text
+                .lines()
+                .enumerate()
+                .filter_map(|(line, text)| parse_include(text).map(|path| (line, path)))
+                .map(|(line, path)| {
+                    Ok(ShaderInclude {
+                        line,
+                        path: path.to_string(),
+                        shader: import_handle(path)?,
+                    })
+                })
+                .collect::<Result<Vec<_>, serde::de::value::Error>>()
+        });
+        let includes = includes.map_err(|e| Box::new(e) as Box<dyn std::error::Error + Send>)?;
+        let build_deps = includes
+            .iter()
+            .map(|include| AssetRef::Path(PathBuf::from(&include.path)))
+            .collect();
+
+        let id = state.id();
+
+        Ok(ImporterValue {
+            assets: vec![ImportedAsset {
+                id,
+                search_tags: vec![],
+                build_deps,
+                load_deps,
+                build_pipeline: None,
+                asset_data: Box::new(ShaderSource {
+                    source: source_text,
+                    includes,
+                }),
+            }],
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy_utils::HashMap;
+
+    use super::*;
+    use crate::plugin::in_memory_app;
+
+    /// Adds the shaders, whose includes name other shaders of `sources`, and resolves the first one.
+    fn resolve_first(sources: &[(&str, &str)]) -> String {
+        let mut app = in_memory_app(MemoryAssetSource::new());
+        app.add_asset::<ShaderSource>();
+        let mut shaders = app
+            .world
+            .get_resource_mut::<Assets<ShaderSource>>()
+            .unwrap();
+
+        let handles: HashMap<_, _> = sources
+            .iter()
+            .map(|(name, _)| {
+                let shader = ShaderSource {
+                    source: String::new(),
+                    includes: Vec::new(),
+                };
+                (*name, shaders.add(shader))
+            })
+            .collect();
+        for (name, source) in sources {
+            let includes = source
+                .lines()
+                .enumerate()
+                .filter_map(|(line, text)| parse_include(text).map(|path| (line, path)))
+                .map(|(line, path)| ShaderInclude {
+                    line,
+                    path: path.to_string(),
+                    shader: handles[path].clone(),
+                })
+                .collect();
+            *shaders.get_mut(&handles[name]).unwrap() = ShaderSource {
+                source: source.to_string(),
+                includes,
+            };
+        }
+
+        ShaderSource::resolve(&handles[sources[0].0], &shaders).unwrap()
+    }
+
+    #[test]
+    fn include_cycle_inserts_each_file_once() {
+        let resolved = resolve_first(&[
+            ("a", "a1\n#include \"b\"\na2"),
+            ("b", "b1\n#include \"a\"\nb2"),
+        ]);
+        assert_eq!(resolved, "a1\nb1\nb2\na2\n");
+    }
+
+    #[test]
+    fn diamond_include_inserts_shared_file_once() {
+        let resolved = resolve_first(&[
+            ("a", "#include \"b\"\n#include <c>\na"),
+            ("b", "#include \"d\"\nb"),
+            ("c", "#include \"d\"\nc"),
+            ("d", "d"),
+        ]);
+        assert_eq!(resolved, "d\nb\nc\na\n");
+    }
+}
@@ -0,0 +1,217 @@
+use std::io::Read;
+
+use bevy_reflect::Uuid;
+use distill_core::{AssetRef, AssetUuid};
+use distill_importer::{ImportedAsset, Importer, ImporterValue, SerdeObj};
+
+use crate::prelude::*;
+use crate::util::{collect_load_deps, LabeledUuidImporterState};
+
+/// An importer for a single asset type, without the boilerplate of distill's [`Importer`].
+///
+/// Register it wrapped in [`Simple`], which takes care of the importer state and of building the
+/// [`ImportedAsset`]s:
+///
+/// ```ignore
+/// app.add_asset_loader(&["png"], Simple(ImageImporter));
+/// ```
+///
+/// Handles deserialized during [`import`](Self::import) are added to the dependencies automatically.
+pub trait SimpleImporter: TypeUuid + Send + Sync + 'static {
+    type Asset: Asset + Serialize;
+
+    /// Increase this when the imported assets change, so the daemon imports the sources again.
+    const VERSION: u32 = 1;
+
+    fn import(
+        &self,
+        bytes: &[u8],
+        ctx: &mut ImportContext,
+    ) -> distill_importer::Result<Self::Asset>;
+}
+
+/// Everything a [`SimpleImporter`] can add to an import besides the asset itself.
+pub struct ImportContext<'a> {
+    state: &'a mut LabeledUuidImporterState,
+    search_tags: Vec<(String, Option<String>)>,
+    load_deps: Vec<AssetRef>,
+    sub_assets: Vec<(String, ImportedAsset)>,
+}
+
+impl<'a> ImportContext<'a> {
+    /// Adds a sub-asset which can be loaded with `path#label@<type uuid>`, and returns its UUID.
+    ///
+    /// The UUID is kept in the importer state, so it stays the same across imports.
+    /// Adding a sub-asset with the same label again replaces it.
+    pub fn add_labeled_sub_asset<T: Asset + Serialize>(
+        &mut self,
+        label: &str,
+        asset: T,
+    ) -> AssetUuid {
+        let id = self.state.label_id(label);
+        let asset_data: Box<dyn SerdeObj> = Box::new(asset);
+        let sub_asset = ImportedAsset {
+            id,
+            // `path#label@type` is resolved through the `name` tag
+            search_tags: vec![("name".to_string(), Some(label.to_string()))],
+            build_deps: vec![],
+            load_deps: vec![],
+            build_pipeline: None,
+            asset_data,
+        };
+        self.sub_assets.retain(|(other, _)| other != label);
+        self.sub_assets.push((label.to_string(), sub_asset));
+        id
+    }
+
+    /// Makes the loader request `asset` together with the imported asset.
+    pub fn add_dependency(&mut self, asset: AssetRef) {
+        if !self.load_deps.contains(&asset) {
+            self.load_deps.push(asset);
+        }
+    }
+
+    /// Adds a search tag to the imported asset.
+    pub fn add_tag(&mut self, key: impl Into<String>, value: Option<String>) {
+        self.search_tags.push((key.into(), value));
+    }
+
+    /// Adds the `name` tag, which is also used for looking up labelled sub-assets.
+    pub fn set_name(&mut self, name: impl Into<String>) {
+        self.search_tags.retain(|(key, _)| key != "name");
+        self.add_tag("name", Some(name.into()));
+    }
+}
+
+/// Adapts a [`SimpleImporter`] to distill's [`Importer`].
+pub struct Simple<T>(pub T);
+
+impl<T: SimpleImporter> TypeUuid for Simple<T> {
+    const TYPE_UUID: Uuid = T::TYPE_UUID;
+}
+
+impl<T: SimpleImporter> Importer for Simple<T> {
+    fn version_static() -> u32
+    where
+        Self: Sized,
+    {
+        T::VERSION
+    }
+
+    fn version(&self) -> u32 {
+        Self::version_static()
+    }
+
+    type Options = ();
+    type State = LabeledUuidImporterState;
+
+    fn import(
+        &self,
+        _: &mut distill_importer::ImportOp,
+        source: &mut dyn Read,
+        _: &Self::Options,
+        state: &mut Self::State,
+    ) -> distill_importer::Result<ImporterValue> {
+        let mut bytes = Vec::new();
+        source.read_to_end(&mut bytes)?;
+
+        let mut ctx = ImportContext {
+            state,
+            search_tags: Vec::new(),
+            load_deps: Vec::new(),
+            sub_assets: Vec::new(),
+        };
+        let (asset, handle_deps) = collect_load_deps(|| self.0.import(&bytes, &mut ctx));
+        let asset = asset?;
+        for dep in handle_deps {
+            ctx.add_dependency(dep);
+        }
+
+        let ImportContext {
+            state,
+            search_tags,
+            load_deps,
+            sub_assets,
+        } = ctx;
+        state.retain_labels(|label| sub_assets.iter().any(|(other, _)| other == label));
+
+        let mut assets = vec![ImportedAsset {
+            id: state.id(),
+            search_tags,
+            build_deps: vec![],
+            load_deps,
+            build_pipeline: None,
+            asset_data: Box::new(asset),
+        }];
+        assets.extend(sub_assets.into_iter().map(|(_, sub_asset)| sub_asset));
+
+        Ok(ImporterValue { assets })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use distill_importer::ImportOp;
+
+    use super::*;
+    use crate::importer::text_importer::Text;
+
+    /// Imports a list of paths, each one a dependency, with the first line as a sub-asset.
+    #[derive(TypeUuid)]
+    #[uuid = "c3a8e4f1-52d7-4b69-8e0a-1f7b6d2c9e35"]
+    struct ManifestImporter;
+
+    impl SimpleImporter for ManifestImporter {
+        type Asset = Text;
+
+        fn import(
+            &self,
+            bytes: &[u8],
+            ctx: &mut ImportContext,
+        ) -> distill_importer::Result<Self::Asset> {
+            let text = std::str::from_utf8(bytes)
+                .map_err(|e| Box::new(e) as Box<dyn std::error::Error + Send>)?;
+            for path in text.lines() {
+                ctx.add_dependency(AssetRef::Path(PathBuf::from(path)));
+            }
+            let first = text.lines().next().unwrap_or_default();
+            ctx.add_labeled_sub_asset("first", Text(first.to_string()));
+            ctx.set_name("manifest");
+            Ok(Text(text.to_string()))
+        }
+    }
+
+    #[test]
+    fn imports_through_context() {
+        let mut state = LabeledUuidImporterState::default();
+        let id = state.id();
+        let first_id = state.label_id("first");
+
+        let value = Simple(ManifestImporter)
+            .import(
+                &mut ImportOp::default(),
+                &mut &b"a.txt\nb.txt\na.txt"[..],
+                &(),
+                &mut state,
+            )
+            .unwrap();
+
+        // the UUIDs in the state are kept
+        let ids: Vec<_> = value.assets.iter().map(|asset| asset.id).collect();
+        assert_eq!(ids, [id, first_id]);
+        assert_eq!(
+            value.assets[0].load_deps,
+            [
+                AssetRef::Path(PathBuf::from("a.txt")),
+                AssetRef::Path(PathBuf::from("b.txt")),
+            ]
+        );
+        assert_eq!(
+            value.assets[0].search_tags,
+            [("name".to_string(), Some("manifest".to_string()))]
+        );
+        assert!(value.assets[1].load_deps.is_empty());
+    }
+}
@@ -1,3 +1,5 @@
+use std::fmt;
+use std::io::Read;
 use std::ops::Deref;
 
 use crate::prelude::*;
@@ -20,30 +22,190 @@ impl Deref for Text {
     }
 }
 
+/// Options of the [`TextImporter`], set in the `importer_options` of a `.meta` file.
+#[derive(TypeUuid, Serialize, Deserialize, Clone, Debug)]
+#[serde(default)]
+#[uuid = "b0502e11-9dfe-432c-91fc-be99bc9633d0"]
+pub struct TextImporterOptions {
+    pub encoding: TextEncoding,
+    pub newlines: Newlines,
+    /// Remove a byte order mark from the start of the file. A UTF-16 byte order mark
+    /// overrides the `encoding`.
+    pub detect_bom: bool,
+    /// Fail the import of files larger than this many bytes.
+    pub max_size: Option<u64>,
+}
+
+impl Default for TextImporterOptions {
+    fn default() -> Self {
+        TextImporterOptions {
+            encoding: TextEncoding::default(),
+            newlines: Newlines::default(),
+            detect_bom: true,
+            max_size: None,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TextEncoding {
+    /// Fail the import on invalid UTF-8.
+    Utf8,
+    /// Replace invalid UTF-8 with `U+FFFD`.
+    Utf8Lossy,
+    /// ISO-8859-1, where every byte is the code point of the same value.
+    Latin1,
+    Utf16Le,
+    Utf16Be,
+}
+
+impl Default for TextEncoding {
+    fn default() -> Self {
+        TextEncoding::Utf8
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Newlines {
+    /// Keep line endings as they are in the file.
+    Keep,
+    /// Convert `\r\n` to `\n`.
+    Lf,
+    /// Convert `\n` to `\r\n`.
+    CrLf,
+}
+
+impl Default for Newlines {
+    fn default() -> Self {
+        Newlines::Keep
+    }
+}
+
+#[derive(Debug)]
+pub enum TextImportError {
+    Io(std::io::Error),
+    InvalidUtf8(std::string::FromUtf8Error),
+    InvalidUtf16(std::char::DecodeUtf16Error),
+    /// UTF-16 text with an odd number of bytes.
+    TruncatedUtf16,
+    /// The file is larger than [`TextImporterOptions::max_size`].
+    TooLarge {
+        max_size: u64,
+    },
+}
+
+impl fmt::Display for TextImportError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TextImportError::Io(e) => write!(f, "{}", e),
+            TextImportError::InvalidUtf8(e) => write!(f, "{}", e),
+            TextImportError::InvalidUtf16(e) => write!(f, "{}", e),
+            TextImportError::TruncatedUtf16 => write!(f, "UTF-16 text with an odd number of bytes"),
+            TextImportError::TooLarge { max_size } => {
+                write!(f, "text file is larger than {} bytes", max_size)
+            }
+        }
+    }
+}
+
+impl std::error::Error for TextImportError {}
+
+impl TextImporterOptions {
+    fn read(&self, source: &mut dyn Read) -> Result<Vec<u8>, TextImportError> {
+        let mut bytes = Vec::new();
+        match self.max_size {
+            Some(max_size) => {
+                // read one byte more than allowed to notice files that are too large
+                source
+                    .take(max_size.saturating_add(1))
+                    .read_to_end(&mut bytes)
+                    .map_err(TextImportError::Io)?;
+                if bytes.len() as u64 > max_size {
+                    return Err(TextImportError::TooLarge { max_size });
+                }
+            }
+            None => {
+                source
+                    .read_to_end(&mut bytes)
+                    .map_err(TextImportError::Io)?;
+            }
+        }
+        Ok(bytes)
+    }
+
+    fn decode(&self, mut bytes: Vec<u8>) -> Result<String, TextImportError> {
+        let mut encoding = self.encoding;
+        if self.detect_bom {
+            let bom = match bytes.as_slice() {
+                [0xEF, 0xBB, 0xBF, ..] => 3,
+                [0xFF, 0xFE, ..] => {
+                    encoding = TextEncoding::Utf16Le;
+                    2
+                }
+                [0xFE, 0xFF, ..] => {
+                    encoding = TextEncoding::Utf16Be;
+                    2
+                }
+                _ => 0,
+            };
+            bytes.drain(..bom);
+        }
+
+        let string = match encoding {
+            TextEncoding::Utf8 => String::from_utf8(bytes).map_err(TextImportError::InvalidUtf8)?,
+            TextEncoding::Utf8Lossy => String::from_utf8_lossy(&bytes).into_owned(),
+            TextEncoding::Latin1 => bytes.into_iter().map(char::from).collect(),
+            TextEncoding::Utf16Le => decode_utf16(&bytes, u16::from_le_bytes)?,
+            TextEncoding::Utf16Be => decode_utf16(&bytes, u16::from_be_bytes)?,
+        };
+        Ok(match self.newlines {
+            Newlines::Keep => string,
+            Newlines::Lf => string.replace("\r\n", "\n"),
+            Newlines::CrLf => string.replace("\r\n", "\n").replace('\n', "\r\n"),
+        })
+    }
+}
+
+fn decode_utf16(bytes: &[u8], from_bytes: fn([u8; 2]) -> u16) -> Result<String, TextImportError> {
+    if bytes.len() % 2 != 0 {
+        return Err(TextImportError::TruncatedUtf16);
+    }
+    let units = bytes
+        .chunks_exact(2)
+        .map(|unit| from_bytes([unit[0], unit[1]]));
+    char::decode_utf16(units)
+        .collect::<Result<String, _>>()
+        .map_err(TextImportError::InvalidUtf16)
+}
+
 impl Importer for TextImporter {
     fn version_static() -> u32
     where
         Self: Sized,
     {
-        1
+        // 2: added `TextImporterOptions`
+        // 3: byte order marks are removed by default
+        3
     }
 
     fn version(&self) -> u32 {
-        1
+        Self::version_static()
     }
 
-    type Options = ();
+    type Options = TextImporterOptions;
     type State = AssetUuidImporterState;
 
     fn import(
         &self,
         _: &mut distill_importer::ImportOp,
         source: &mut dyn std::io::Read,
-        _: &Self::Options,
+        options: &Self::Options,
         state: &mut Self::State,
     ) -> Result<ImporterValue, distill_importer::Error> {
-        let mut string = String::new();
-        source.read_to_string(&mut string)?;
+        let string = options
+            .read(source)
+            .and_then(|bytes| options.decode(bytes))
+            .map_err(|e| Box::new(e) as Box<dyn std::error::Error + Send>)?;
 
         let id = state.id();
 
@@ -59,3 +221,48 @@ impl Importer for TextImporter {
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use distill_importer::ImportOp;
+
+    use super::*;
+    use crate::io::import::import_source;
+
+    #[test]
+    fn applies_options_from_meta_file() {
+        let source =
+            import_source(&TextImporter, Path::new("assets/text/crlf.txt"), None, None).unwrap();
+        let ids: Vec<_> = source
+            .asset_ids()
+            .map(|id| uuid::Uuid::from_bytes(id.0).to_string())
+            .collect();
+        assert_eq!(ids, ["41cea5fe-2344-4f42-bdda-35ae6cec94d7"]);
+
+        let data = bincode::serialize(&source.assets()[0].asset_data).unwrap();
+        let text: String = bincode::deserialize(&data).unwrap();
+        // the byte order mark is removed by default
+        assert_eq!(text, "first line\nsecond line\n");
+    }
+
+    #[test]
+    fn rejects_files_over_max_size() {
+        let options = TextImporterOptions {
+            max_size: Some(4),
+            ..Default::default()
+        };
+        let error = TextImporter
+            .import(
+                &mut ImportOp::default(),
+                &mut &b"hello"[..],
+                &options,
+                &mut AssetUuidImporterState::default(),
+            )
+            .err()
+            .unwrap()
+            .to_string();
+        assert_eq!(error, "text file is larger than 4 bytes");
+    }
+}
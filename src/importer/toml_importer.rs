@@ -0,0 +1,139 @@
+use std::marker::PhantomData;
+
+use bevy_reflect::Uuid;
+use distill_importer::{ImportedAsset, Importer, ImporterValue};
+
+use crate::prelude::*;
+use crate::util::{collect_load_deps, importer_uuid, AssetUuidImporterState};
+
+pub struct TomlImporter<A: Asset + Serialize>(PhantomData<A>);
+#[cfg(feature = "toml-importer")]
+impl<A: Asset + Serialize> TypeUuid for TomlImporter<A> {
+    /// Derived from the asset type, so each `TomlImporter<A>` is a different importer.
+    const TYPE_UUID: Uuid = Uuid::from_bytes(importer_uuid(
+        &[
+            170, 79, 25, 82, 48, 172, 71, 90, 135, 225, 43, 6, 13, 119, 91, 50,
+        ],
+        A::TYPE_UUID.as_bytes(),
+    ));
+}
+
+#[cfg(feature = "toml-importer")]
+impl<A: Asset + Serialize> TomlImporter<A> {
+    pub fn new() -> Self {
+        TomlImporter(PhantomData)
+    }
+}
+#[cfg(feature = "toml-importer")]
+impl<A: Asset + Serialize + for<'de> Deserialize<'de>> Importer for TomlImporter<A> {
+    fn version_static() -> u32
+    where
+        Self: Sized,
+    {
+        // 2: fills `load_deps` with the handles in the source
+        2
+    }
+
+    fn version(&self) -> u32 {
+        Self::version_static()
+    }
+
+    type Options = ();
+    type State = AssetUuidImporterState;
+
+    fn import(
+        &self,
+        _: &mut distill_importer::ImportOp,
+        source: &mut dyn std::io::Read,
+        _: &Self::Options,
+        state: &mut Self::State,
+    ) -> distill_importer::Result<ImporterValue> {
+        let mut string = String::new();
+        source.read_to_string(&mut string)?;
+
+        // the error names the key that failed to deserialize, like `units[2].health`
+        let mut deserializer = toml::Deserializer::new(&string);
+        let (data, load_deps) =
+            collect_load_deps(|| serde_path_to_error::deserialize(&mut deserializer));
+        let data: A = data.map_err(|e| Box::new(e) as Box<dyn std::error::Error + Send>)?;
+
+        let id = state.id();
+
+        Ok(ImporterValue {
+            assets: vec![ImportedAsset {
+                id,
+                search_tags: vec![],
+                build_deps: vec![],
+                load_deps,
+                build_pipeline: None,
+                asset_data: Box::new(data),
+            }],
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+
+    use distill_core::AssetRef;
+    use distill_importer::ImportOp;
+
+    use super::*;
+    use crate::importer::text_importer::Text;
+    use crate::io::import::import_source;
+
+    #[derive(Serialize, Deserialize, TypeUuid)]
+    #[uuid = "5c0b6f0e-96a4-4b8e-9a0e-2f64a3d2c1f4"]
+    struct Balance {
+        description: Handle<Text>,
+        units: Vec<Unit>,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    struct Unit {
+        name: String,
+        health: u32,
+        damage: u32,
+    }
+
+    #[test]
+    fn imports_config_with_handle() {
+        // handles are only created inside of the serde context of an import
+        let source = import_source(
+            &TomlImporter::<Balance>::new(),
+            Path::new("assets/config/balance.toml"),
+            None,
+            None,
+        )
+        .unwrap();
+        assert_eq!(source.assets().len(), 1);
+        assert_eq!(
+            source.assets()[0].load_deps,
+            [AssetRef::Path(PathBuf::from("lorem_ipsum.txt"))]
+        );
+    }
+
+    #[test]
+    fn error_names_failing_key() {
+        let source = r#"
+            description = "lorem_ipsum.txt"
+
+            [[units]]
+            name = "archer"
+            health = "forty"
+            damage = 12
+        "#;
+        let error = TomlImporter::<Balance>::new()
+            .import(
+                &mut ImportOp::default(),
+                &mut source.as_bytes(),
+                &(),
+                &mut AssetUuidImporterState::default(),
+            )
+            .err()
+            .unwrap()
+            .to_string();
+        assert!(error.contains("units[0].health"), "{}", error);
+    }
+}
@@ -0,0 +1,115 @@
+use std::marker::PhantomData;
+
+use bevy_reflect::Uuid;
+use distill_importer::{ImportedAsset, Importer, ImporterValue};
+
+use crate::prelude::*;
+use crate::util::{collect_load_deps, importer_uuid, AssetUuidImporterState};
+
+pub struct YamlImporter<A: Asset + Serialize>(PhantomData<A>);
+#[cfg(feature = "yaml-importer")]
+impl<A: Asset + Serialize> TypeUuid for YamlImporter<A> {
+    /// Derived from the asset type, so each `YamlImporter<A>` is a different importer.
+    const TYPE_UUID: Uuid = Uuid::from_bytes(importer_uuid(
+        &[
+            137, 186, 1, 123, 108, 203, 73, 61, 149, 100, 229, 28, 210, 85, 73, 212,
+        ],
+        A::TYPE_UUID.as_bytes(),
+    ));
+}
+
+#[cfg(feature = "yaml-importer")]
+impl<A: Asset + Serialize> YamlImporter<A> {
+    pub fn new() -> Self {
+        YamlImporter(PhantomData)
+    }
+}
+#[cfg(feature = "yaml-importer")]
+impl<A: Asset + Serialize + for<'de> Deserialize<'de>> Importer for YamlImporter<A> {
+    fn version_static() -> u32
+    where
+        Self: Sized,
+    {
+        // 2: fills `load_deps` with the handles in the source
+        2
+    }
+
+    fn version(&self) -> u32 {
+        Self::version_static()
+    }
+
+    type Options = ();
+    type State = AssetUuidImporterState;
+
+    fn import(
+        &self,
+        _: &mut distill_importer::ImportOp,
+        source: &mut dyn std::io::Read,
+        _: &Self::Options,
+        state: &mut Self::State,
+    ) -> distill_importer::Result<ImporterValue> {
+        // the error names the key that failed to deserialize, like `greetings.en`
+        let deserializer = serde_yaml::Deserializer::from_reader(source);
+        let (data, load_deps) =
+            collect_load_deps(|| serde_path_to_error::deserialize(deserializer));
+        let data: A = data.map_err(|e| Box::new(e) as Box<dyn std::error::Error + Send>)?;
+
+        let id = state.id();
+
+        Ok(ImporterValue {
+            assets: vec![ImportedAsset {
+                id,
+                search_tags: vec![],
+                build_deps: vec![],
+                load_deps,
+                build_pipeline: None,
+                asset_data: Box::new(data),
+            }],
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use distill_importer::ImportOp;
+
+    use super::*;
+
+    #[derive(Serialize, Deserialize, TypeUuid)]
+    #[uuid = "0e8d2a55-7b3c-4f1e-b6a9-4d2c8f1e7a30"]
+    struct Localization {
+        language: String,
+        strings: BTreeMap<String, String>,
+    }
+
+    fn import(source: &str) -> distill_importer::Result<ImporterValue> {
+        YamlImporter::<Localization>::new().import(
+            &mut ImportOp::default(),
+            &mut source.as_bytes(),
+            &(),
+            &mut AssetUuidImporterState::default(),
+        )
+    }
+
+    #[test]
+    fn imports_config() {
+        let source = std::fs::read_to_string("assets/config/localization.yaml").unwrap();
+        let mut value = import(&source).unwrap();
+        assert_eq!(value.assets.len(), 1);
+
+        let data = bincode::serialize(&value.assets.remove(0).asset_data).unwrap();
+        let localization: Localization = bincode::deserialize(&data).unwrap();
+        assert_eq!(localization.language, "en");
+        assert_eq!(localization.strings["menu.start"], "Start game");
+        assert_eq!(localization.strings["menu.quit"], "Quit");
+    }
+
+    #[test]
+    fn error_names_failing_key() {
+        let source = "language: en\nstrings:\n  menu.start: [Start, game]\n";
+        let error = import(source).err().unwrap().to_string();
+        assert!(error.contains("strings.menu.start"), "{}", error);
+    }
+}
@@ -0,0 +1,540 @@
+use std::collections::HashSet;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant, SystemTime};
+
+use bevy_tasks::TaskPool;
+use bevy_utils::HashMap;
+use distill_core::{ArtifactMetadata, AssetMetadata, AssetRef, AssetUuid};
+use distill_loader::crossbeam_channel::{unbounded, Receiver, Sender};
+use distill_loader::io::{DataRequest, LoaderIO, MetadataRequest, ResolveRequest};
+use distill_loader::loader::LoaderState;
+
+use super::identifier_path;
+use super::import::{self, ImportedArtifact, SharedImporter, SharedImporters};
+use super::layered::LayerIndex;
+use crate::plugin::AssetLoader;
+use crate::processor::SharedProcessors;
+use crate::schema::SharedSchemas;
+
+const WATCH_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Debug)]
+pub(crate) enum DirectoryIOError {
+    AssetNotFound(AssetUuid),
+    PathNotFound(String),
+}
+
+impl fmt::Display for DirectoryIOError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DirectoryIOError::AssetNotFound(id) => write!(f, "no asset with id {:?}", id),
+            DirectoryIOError::PathNotFound(path) => write!(f, "no asset at path {}", path),
+        }
+    }
+}
+
+impl std::error::Error for DirectoryIOError {}
+
+struct SourceEntry {
+    modified: Option<SystemTime>,
+    assets: Vec<AssetUuid>,
+    /// The sources in the `build_deps` of the assets, which are imported again when one of these changes.
+    build_deps: Vec<PathBuf>,
+    /// The serialized importer state of the last import, which keeps the UUIDs of sources without a `.meta` file.
+    state: Vec<u8>,
+}
+
+/// The imported contents of an asset directory. Paths are relative to the asset root.
+#[derive(Default)]
+pub(crate) struct DirectoryContents {
+    assets: HashMap<AssetUuid, (PathBuf, ImportedArtifact)>,
+    sources: HashMap<PathBuf, SourceEntry>,
+    /// Sources whose last import failed, with the importer and modification time it failed with.
+    /// They are only imported again once one of them changes.
+    failed: HashMap<PathBuf, (usize, Option<SystemTime>)>,
+}
+
+impl LayerIndex for RwLock<DirectoryContents> {
+    fn contains_asset(&self, id: &AssetUuid) -> bool {
+        self.read().unwrap().assets.contains_key(id)
+    }
+
+    fn contains_path(&self, path: &Path) -> bool {
+        self.read().unwrap().sources.contains_key(path)
+    }
+
+    fn artifact_metadata(&self, id: &AssetUuid) -> Option<ArtifactMetadata> {
+        self.read().unwrap().asset_metadata(id)?.artifact
+    }
+}
+
+impl DirectoryContents {
+    fn resolve_path(&self, path: &Path) -> Option<AssetUuid> {
+        self.sources
+            .get(path)
+            .and_then(|source| source.assets.first().copied())
+    }
+
+    fn asset_metadata(&self, id: &AssetUuid) -> Option<AssetMetadata> {
+        let (source, asset) = self.assets.get(id)?;
+        let mut metadata = asset.metadata.clone();
+        metadata.artifact = Some(import::resolve_load_deps(
+            asset.artifact(),
+            source,
+            |path| self.resolve_path(path),
+        ));
+        Some(metadata)
+    }
+}
+
+/// The changes found by a scan of the asset directory.
+#[derive(Default)]
+struct SourceChanges {
+    assets: Vec<AssetUuid>,
+    paths: Vec<PathBuf>,
+}
+
+/// A [`LoaderIO`] which imports the files of an asset directory in-process.
+///
+/// The importers registered through [`AddAsset`](crate::AddAsset) are run on a task pool
+/// once the app has started. Requests made before the first scan has finished are queued.
+/// Everything is kept in memory, nothing is written to disk.
+pub(crate) struct DirectoryIO {
+    root: PathBuf,
+    watch: bool,
+    deterministic_uuids: bool,
+    importers: SharedImporters,
+    schemas: SharedSchemas,
+    /// `None` if raw assets are served.
+    processors: Option<SharedProcessors>,
+    task_pool: TaskPool,
+    contents: Arc<RwLock<DirectoryContents>>,
+
+    scanning: bool,
+    initial_scan_done: bool,
+    last_scan: Option<Instant>,
+    /// The number of registered importers at the last scan, files skipped before might have one now.
+    scanned_importers: usize,
+    changes: (Sender<SourceChanges>, Receiver<SourceChanges>),
+
+    pending_metadata: Vec<MetadataRequest>,
+    pending_resolve: Vec<ResolveRequest>,
+    pending_data: Vec<DataRequest>,
+}
+
+impl DirectoryIO {
+    pub fn new(
+        root: PathBuf,
+        watch: bool,
+        deterministic_uuids: bool,
+        importers: SharedImporters,
+        schemas: SharedSchemas,
+        processors: Option<SharedProcessors>,
+        task_pool: TaskPool,
+    ) -> Self {
+        DirectoryIO {
+            root,
+            watch,
+            deterministic_uuids,
+            importers,
+            schemas,
+            processors,
+            task_pool,
+            contents: Arc::default(),
+            scanning: false,
+            initial_scan_done: false,
+            last_scan: None,
+            scanned_importers: 0,
+            changes: unbounded(),
+            pending_metadata: Vec::new(),
+            pending_resolve: Vec::new(),
+            pending_data: Vec::new(),
+        }
+    }
+
+    pub fn contents(&self) -> Arc<RwLock<DirectoryContents>> {
+        Arc::clone(&self.contents)
+    }
+
+    fn start_scan(&mut self) {
+        self.scanning = true;
+        self.last_scan = Some(Instant::now());
+        // registering an importer waits for the lock, so it isn't held while importing
+        let importers = self.importers.read().unwrap().clone();
+        self.scanned_importers = importers.len();
+
+        let root = self.root.clone();
+        let deterministic_uuids = self.deterministic_uuids;
+        let schemas = Arc::clone(&self.schemas);
+        let processors = self.processors.clone();
+        let contents = Arc::clone(&self.contents);
+        let sender = self.changes.0.clone();
+        self.task_pool
+            .spawn(async move {
+                let changes = scan_directory(
+                    &root,
+                    deterministic_uuids,
+                    &importers,
+                    &schemas,
+                    processors.as_ref(),
+                    &contents,
+                );
+                let _ = sender.send(changes);
+            })
+            .detach();
+    }
+
+    fn serve_metadata(&self, request: MetadataRequest) {
+        let contents = self.contents.read().unwrap();
+
+        let mut visited = HashSet::new();
+        let mut stack: Vec<AssetUuid> = request.requested_assets().copied().collect();
+        let mut artifacts = Vec::new();
+        while let Some(id) = stack.pop() {
+            if !visited.insert(id) {
+                continue;
+            }
+            if let Some(artifact) = contents.asset_metadata(&id).and_then(|m| m.artifact) {
+                stack.extend(artifact.load_deps.iter().filter_map(|dep| match dep {
+                    AssetRef::Uuid(uuid) => Some(*uuid),
+                    AssetRef::Path(_) => None,
+                }));
+                artifacts.push(artifact);
+            }
+        }
+
+        request.complete(artifacts);
+    }
+
+    fn serve_resolve(&self, request: ResolveRequest) {
+        let contents = self.contents.read().unwrap();
+
+        let path = identifier_path(request.identifier()).to_string();
+        match contents.sources.get(Path::new(&path)) {
+            Some(source) => {
+                let metadata = source
+                    .assets
+                    .iter()
+                    .filter_map(|id| contents.asset_metadata(id))
+                    .collect();
+                request.complete(vec![(PathBuf::from(path), metadata)]);
+            }
+            None => request.error(DirectoryIOError::PathNotFound(path)),
+        }
+    }
+
+    fn serve_data(&self, request: DataRequest) {
+        let contents = self.contents.read().unwrap();
+
+        let id = request.asset_id();
+        match contents.assets.get(&id) {
+            Some((_, asset)) => request.complete(asset.data.clone()),
+            None => request.error(DirectoryIOError::AssetNotFound(id)),
+        }
+    }
+}
+
+impl LoaderIO for DirectoryIO {
+    fn get_asset_metadata_with_dependencies(&mut self, request: MetadataRequest) {
+        if self.initial_scan_done {
+            self.serve_metadata(request);
+        } else {
+            self.pending_metadata.push(request);
+        }
+    }
+
+    fn get_asset_candidates(&mut self, requests: Vec<ResolveRequest>) {
+        if self.initial_scan_done {
+            requests.into_iter().for_each(|r| self.serve_resolve(r));
+        } else {
+            self.pending_resolve.extend(requests);
+        }
+    }
+
+    fn get_artifacts(&mut self, requests: Vec<DataRequest>) {
+        if self.initial_scan_done {
+            requests.into_iter().for_each(|r| self.serve_data(r));
+        } else {
+            self.pending_data.extend(requests);
+        }
+    }
+
+    fn tick(&mut self, loader: &mut LoaderState) {
+        let scan_due = match self.last_scan {
+            None => true,
+            Some(last_scan) => {
+                (self.watch && last_scan.elapsed() >= WATCH_INTERVAL)
+                    || self.importers.read().unwrap().len() != self.scanned_importers
+            }
+        };
+        if !self.scanning && scan_due {
+            self.start_scan();
+        }
+
+        for changes in self.changes.1.try_iter() {
+            self.scanning = false;
+            // nothing can be loaded before the initial scan, so only rescans invalidate assets
+            if self.initial_scan_done {
+                if !changes.assets.is_empty() {
+                    loader.invalidate_assets(&changes.assets);
+                }
+                if !changes.paths.is_empty() {
+                    loader.invalidate_paths(&changes.paths);
+                }
+            }
+            self.initial_scan_done = true;
+        }
+
+        if self.initial_scan_done {
+            for request in std::mem::take(&mut self.pending_metadata) {
+                self.serve_metadata(request);
+            }
+            for request in std::mem::take(&mut self.pending_resolve) {
+                self.serve_resolve(request);
+            }
+            for request in std::mem::take(&mut self.pending_data) {
+                self.serve_data(request);
+            }
+        }
+    }
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+/// Imports every file below `root` that changed since the last scan and updates `contents`.
+fn scan_directory(
+    root: &Path,
+    deterministic_uuids: bool,
+    importers: &[AssetLoader],
+    schemas: &SharedSchemas,
+    processors: Option<&SharedProcessors>,
+    contents: &RwLock<DirectoryContents>,
+) -> SourceChanges {
+    let mut files = Vec::new();
+    if let Err(e) = import::collect_sources(root, &mut files) {
+        bevy_log::error!("failed to read asset dir {}: {}", root.display(), e);
+    }
+
+    let mut changes = SourceChanges::default();
+
+    let sources: Vec<_> = files
+        .into_iter()
+        .filter_map(|path| {
+            let importer = import::find_importer(importers, &path)?;
+            let relative = path.strip_prefix(root).unwrap_or(&path).to_path_buf();
+            let last_modified = modified(&path).max(modified(&import::meta_path(&path)));
+            Some((path, relative, importer, last_modified))
+        })
+        .collect();
+    let seen: HashSet<PathBuf> = sources
+        .iter()
+        .map(|(_, relative, ..)| relative.clone())
+        .collect();
+    let dirty = dirty_sources(&sources, &seen, &contents.read().unwrap());
+
+    let mut imported = Vec::new();
+    // retried once the source or its importer changes, instead of on every scan
+    let mut failed = Vec::new();
+    for (path, relative, importer, last_modified) in sources {
+        if !dirty.contains(&relative) {
+            continue;
+        }
+        let importer_index = importer;
+        let importer = SharedImporter::new(&importers[importer].1, schemas, processors);
+
+        // copied, so the state survives a failed import and keeps the UUIDs for the next one
+        let previous_state = contents
+            .read()
+            .unwrap()
+            .sources
+            .get(&relative)
+            .map(|source| source.state.clone());
+        let uuid_root = deterministic_uuids.then(|| root);
+        match import::import_source(&importer, &path, uuid_root, previous_state.as_deref()) {
+            Ok(source) => imported.push((relative, importer_index, last_modified, source)),
+            Err(e) => {
+                bevy_log::error!("{}", e);
+                failed.push((relative, (importer_index, last_modified)));
+            }
+        }
+    }
+
+    // handles can only be serialized once the sources they point at have been imported
+    let fresh: HashMap<PathBuf, AssetUuid> = imported
+        .iter()
+        .filter_map(|(relative, _, _, source)| Some((relative.clone(), source.asset_ids().next()?)))
+        .collect();
+    let serialized: Vec<_> = {
+        let contents = contents.read().unwrap();
+        let resolve_path = |path: &Path| {
+            fresh
+                .get(path)
+                .copied()
+                .or_else(|| contents.resolve_path(path))
+        };
+        imported
+            .into_iter()
+            .filter_map(|(relative, importer, last_modified, mut source)| {
+                match source.serialize(&relative, &resolve_path) {
+                    Ok(artifacts) => Some((relative, last_modified, source.state, artifacts)),
+                    Err(e) => {
+                        bevy_log::error!("{}", e);
+                        failed.push((relative, (importer, last_modified)));
+                        None
+                    }
+                }
+            })
+            .collect()
+    };
+    contents.write().unwrap().failed.extend(failed);
+
+    for (relative, last_modified, state, artifacts) in serialized {
+        bevy_log::debug!(
+            "imported {} ({} assets)",
+            relative.display(),
+            artifacts.len()
+        );
+
+        let mut contents = contents.write().unwrap();
+        let build_deps = artifacts
+            .iter()
+            .flat_map(|artifact| &artifact.artifact().build_deps)
+            .filter_map(|dep| match dep {
+                AssetRef::Uuid(id) => contents.assets.get(id).map(|(path, _)| path.clone()),
+                AssetRef::Path(path) => {
+                    // relative to the including source first, then to the root
+                    let sibling = relative.parent().map(|parent| parent.join(path));
+                    sibling
+                        .filter(|sibling| seen.contains(sibling))
+                        .or_else(|| seen.contains(path).then(|| path.clone()))
+                }
+            })
+            .collect();
+        if let Some(old) = contents.sources.remove(&relative) {
+            for id in &old.assets {
+                contents.assets.remove(id);
+            }
+            changes.assets.extend(old.assets);
+        }
+        let ids: Vec<_> = artifacts.iter().map(ImportedArtifact::id).collect();
+        for artifact in artifacts {
+            contents
+                .assets
+                .insert(artifact.id(), (relative.clone(), artifact));
+        }
+        changes.assets.extend(ids.iter().copied());
+        changes.paths.push(relative.clone());
+        contents.failed.remove(&relative);
+        contents.sources.insert(
+            relative,
+            SourceEntry {
+                modified: last_modified,
+                assets: ids,
+                build_deps,
+                state,
+            },
+        );
+    }
+
+    let mut contents = contents.write().unwrap();
+    contents.failed.retain(|path, _| seen.contains(path));
+    let removed: Vec<_> = contents
+        .sources
+        .keys()
+        .filter(|path| !seen.contains(*path))
+        .cloned()
+        .collect();
+    for path in removed {
+        if let Some(source) = contents.sources.remove(&path) {
+            for id in &source.assets {
+                contents.assets.remove(id);
+            }
+            changes.assets.extend(source.assets);
+            changes.paths.push(path);
+        }
+    }
+
+    changes
+}
+
+/// Returns the sources that changed since the last scan, and the sources depending on them
+/// through their `build_deps`. Sources that failed to import are skipped until they or their importer change.
+fn dirty_sources(
+    sources: &[(PathBuf, PathBuf, usize, Option<SystemTime>)],
+    seen: &HashSet<PathBuf>,
+    contents: &DirectoryContents,
+) -> HashSet<PathBuf> {
+    let mut dirty: HashSet<PathBuf> = sources
+        .iter()
+        .filter(|(_, relative, importer, last_modified)| {
+            let failed = contents.failed.get(relative) == Some(&(*importer, *last_modified));
+            !failed
+                && contents
+                    .sources
+                    .get(relative)
+                    .map_or(true, |source| source.modified != *last_modified)
+        })
+        .map(|(_, relative, ..)| relative.clone())
+        .collect();
+    // removed sources change their dependents as well
+    dirty.extend(
+        contents
+            .sources
+            .keys()
+            .filter(|path| !seen.contains(*path))
+            .cloned(),
+    );
+
+    loop {
+        let dependents: Vec<_> = contents
+            .sources
+            .iter()
+            .filter(|(path, source)| {
+                !dirty.contains(*path) && source.build_deps.iter().any(|dep| dirty.contains(dep))
+            })
+            .map(|(path, _)| path.clone())
+            .collect();
+        if dependents.is_empty() {
+            return dirty;
+        }
+        for path in dependents {
+            bevy_log::debug!("{} depends on a changed source", path.display());
+            dirty.insert(path);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn failed_source_is_retried_once_it_or_its_importer_changes() {
+        let failed_at = Some(SystemTime::UNIX_EPOCH);
+        let mut contents = DirectoryContents::default();
+        contents
+            .failed
+            .insert(PathBuf::from("a.txt"), (0, failed_at));
+        let seen = HashSet::from([PathBuf::from("a.txt")]);
+        let dirty = |importer, modified| {
+            let sources = [(
+                PathBuf::from("assets/a.txt"),
+                PathBuf::from("a.txt"),
+                importer,
+                modified,
+            )];
+            !dirty_sources(&sources, &seen, &contents).is_empty()
+        };
+
+        assert!(!dirty(0, failed_at));
+        assert!(dirty(
+            0,
+            Some(SystemTime::UNIX_EPOCH + Duration::from_secs(1))
+        ));
+        assert!(dirty(1, failed_at));
+    }
+}
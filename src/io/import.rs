@@ -0,0 +1,538 @@
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+
+use bevy_utils::HashMap;
+use distill_core::{
+    ArtifactId, ArtifactMetadata, AssetMetadata, AssetRef, AssetTypeId, AssetUuid, CompressionType,
+    TypeUuidDynamic,
+};
+use distill_importer::{
+    BoxedExportInputs, BoxedImporter, BoxedImporterValue, ExportAsset, ImportOp, ImportedAsset,
+    SerdeObj, SourceMetadata,
+};
+use distill_loader::crossbeam_channel::{unbounded, Receiver, Sender};
+use distill_loader::handle::{RefOp, SerdeContext};
+use distill_loader::storage::LoaderInfoProvider;
+use distill_loader::LoadHandle;
+
+use crate::plugin::AssetLoader;
+use crate::processor::SharedProcessors;
+use crate::schema::{self, SharedSchemas};
+use crate::util;
+
+/// The importers registered through [`AddAsset`](crate::AddAsset), shared with everything
+/// that imports assets in-process.
+pub(crate) type SharedImporters = Arc<RwLock<Vec<AssetLoader>>>;
+
+/// Adds an importer to the registry. If another importer handles one of the same extensions,
+/// the one registered last is used.
+///
+/// Logs an error and ignores `importer` if a different importer with the same UUID is registered already.
+pub(crate) fn register_importer(
+    importers: &SharedImporters,
+    extensions: &'static [&'static str],
+    importer: Arc<dyn BoxedImporter>,
+) {
+    let mut importers = importers.write().unwrap();
+    // the UUID identifies the importer in the daemon and in `.meta` files, so it has to be unique
+    let same_uuid = importers.iter().find(|(_, other)| {
+        other.type_uuid() == importer.type_uuid() && other.type_name() != importer.type_name()
+    });
+    if let Some((_, other)) = same_uuid {
+        bevy_log::error!(
+            "importer {} is ignored, importer {} has the same UUID {}",
+            importer.type_name(),
+            other.type_name(),
+            importer.type_uuid()
+        );
+        return;
+    }
+    for extension in extensions {
+        let duplicate = importers
+            .iter()
+            .find(|(other_extensions, _)| other_extensions.contains(extension));
+        if let Some((_, other)) = duplicate {
+            bevy_log::warn!(
+                "importer {} for extension `{}` replaces importer {}",
+                importer.type_name(),
+                extension,
+                other.type_name()
+            );
+        }
+    }
+    importers.push((extensions, importer));
+}
+
+/// Boxes the registered importers for handing them to an asset daemon, which takes ownership of its importers.
+///
+/// Without `processors` the importers produce raw assets.
+pub(crate) fn boxed_importers(
+    importers: &[AssetLoader],
+    schemas: &SharedSchemas,
+    processors: Option<&SharedProcessors>,
+) -> Vec<(&'static [&'static str], Box<dyn BoxedImporter>)> {
+    importers
+        .iter()
+        .map(|(extensions, importer)| {
+            let importer: Box<dyn BoxedImporter> =
+                Box::new(SharedImporter::new(importer, schemas, processors));
+            (*extensions, importer)
+        })
+        .collect()
+}
+
+/// Identifies the importers by their UUIDs, extensions and versions, which include the
+/// registered schema and processor versions, so a daemon can be restarted when one of them changes.
+pub(crate) fn importers_version(
+    importers: &[(&'static [&'static str], Box<dyn BoxedImporter>)],
+) -> u64 {
+    let mut hasher = util::StableHasher::new();
+    for (extensions, importer) in importers {
+        hasher.write(importer.type_uuid().as_bytes());
+        hasher.write_u32(importer.version());
+        for extension in extensions.iter() {
+            hasher.write(extension.as_bytes());
+            hasher.write(&[0]);
+        }
+    }
+    hasher.finish()
+}
+
+/// A [`BoxedImporter`] which is also kept in the importer registry.
+///
+/// Runs the registered [`AssetProcessor`](crate::processor::AssetProcessor)s on the imported assets,
+/// and writes the schema version in front of assets of types with a registered [`AssetSchema`](crate::schema::AssetSchema).
+pub(crate) struct SharedImporter {
+    importer: Arc<dyn BoxedImporter>,
+    schemas: SharedSchemas,
+    processors: Option<SharedProcessors>,
+}
+
+impl SharedImporter {
+    pub fn new(
+        importer: &Arc<dyn BoxedImporter>,
+        schemas: &SharedSchemas,
+        processors: Option<&SharedProcessors>,
+    ) -> Self {
+        SharedImporter {
+            importer: Arc::clone(importer),
+            schemas: Arc::clone(schemas),
+            processors: processors.map(Arc::clone),
+        }
+    }
+}
+
+impl TypeUuidDynamic for SharedImporter {
+    fn type_uuid(&self) -> bevy_reflect::Uuid {
+        self.importer.type_uuid()
+    }
+
+    fn type_name(&self) -> &'static str {
+        self.importer.type_name()
+    }
+}
+
+impl BoxedImporter for SharedImporter {
+    fn import_boxed(
+        &self,
+        op: &mut ImportOp,
+        source: &mut dyn std::io::Read,
+        options: Box<dyn SerdeObj>,
+        state: Box<dyn SerdeObj>,
+    ) -> distill_importer::Result<BoxedImporterValue> {
+        let mut imported = self.importer.import_boxed(op, source, options, state)?;
+        let mut assets = std::mem::take(&mut imported.value.assets);
+        if let Some(processors) = &self.processors {
+            assets = processors
+                .process_assets(assets)
+                .map_err(|e| Box::new(e) as Box<dyn std::error::Error + Send>)?;
+        }
+        imported.value.assets = schema::version_assets(assets, &self.schemas);
+        Ok(imported)
+    }
+
+    fn export_boxed(
+        &self,
+        output: &mut dyn std::io::Write,
+        options: Box<dyn SerdeObj>,
+        state: Box<dyn SerdeObj>,
+        assets: Vec<ExportAsset>,
+    ) -> distill_importer::Result<BoxedExportInputs> {
+        self.importer.export_boxed(output, options, state, assets)
+    }
+
+    fn default_options(&self) -> Box<dyn SerdeObj> {
+        self.importer.default_options()
+    }
+
+    fn default_state(&self) -> Box<dyn SerdeObj> {
+        self.importer.default_state()
+    }
+
+    fn version(&self) -> u32 {
+        let version = schema::importer_version(self.importer.version(), &self.schemas);
+        match &self.processors {
+            Some(processors) => processors.importer_version(version),
+            None => version,
+        }
+    }
+
+    fn deserialize_metadata(
+        &self,
+        bytes: &[u8],
+    ) -> distill_importer::Result<SourceMetadata<Box<dyn SerdeObj>, Box<dyn SerdeObj>>> {
+        self.importer.deserialize_metadata(bytes)
+    }
+
+    fn deserialize_options(&self, bytes: &[u8]) -> distill_importer::Result<Box<dyn SerdeObj>> {
+        self.importer.deserialize_options(bytes)
+    }
+
+    fn deserialize_state(&self, bytes: &[u8]) -> distill_importer::Result<Box<dyn SerdeObj>> {
+        self.importer.deserialize_state(bytes)
+    }
+}
+
+#[derive(Debug)]
+pub enum ImportError {
+    Io(PathBuf, std::io::Error),
+    Metadata(PathBuf, distill_importer::Error),
+    Import(PathBuf, distill_importer::Error),
+    Serialize(PathBuf, bincode::Error),
+}
+
+impl fmt::Display for ImportError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ImportError::Io(path, e) => write!(f, "failed to read {}: {}", path.display(), e),
+            ImportError::Metadata(path, e) => {
+                write!(f, "invalid metadata for {}: {}", path.display(), e)
+            }
+            ImportError::Import(path, e) => write!(f, "failed to import {}: {}", path.display(), e),
+            ImportError::Serialize(path, e) => {
+                write!(
+                    f,
+                    "failed to serialize asset from {}: {}",
+                    path.display(),
+                    e
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for ImportError {}
+
+/// An imported asset together with its serialized artifact.
+#[derive(Clone)]
+pub(crate) struct ImportedArtifact {
+    pub metadata: AssetMetadata,
+    pub data: Vec<u8>,
+}
+
+impl ImportedArtifact {
+    pub fn id(&self) -> AssetUuid {
+        self.metadata.id
+    }
+
+    pub fn artifact(&self) -> &ArtifactMetadata {
+        self.metadata
+            .artifact
+            .as_ref()
+            .expect("imported assets always have an artifact")
+    }
+}
+
+pub(crate) fn meta_path(path: &Path) -> PathBuf {
+    let mut meta_path = path.as_os_str().to_owned();
+    meta_path.push(".meta");
+    PathBuf::from(meta_path)
+}
+
+/// Collects every file below `dir` that isn't a `.meta` file.
+pub(crate) fn collect_sources(dir: &Path, files: &mut Vec<PathBuf>) -> std::io::Result<()> {
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            collect_sources(&path, files)?;
+        } else if path.extension().map_or(true, |ext| ext != "meta") {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// Returns the index of the importer responsible for `path`.
+///
+/// Extensions can contain dots (like `scene.ron`), in which case the longest match wins.
+pub(crate) fn find_importer<I>(
+    importers: &[(&'static [&'static str], I)],
+    path: &Path,
+) -> Option<usize> {
+    let file_name = path.file_name()?.to_str()?;
+    importers
+        .iter()
+        .enumerate()
+        .flat_map(|(i, (extensions, _))| extensions.iter().map(move |ext| (i, *ext)))
+        .filter(|(_, ext)| {
+            file_name.len() > ext.len() + 1
+                && file_name.ends_with(ext)
+                && file_name[..file_name.len() - ext.len()].ends_with('.')
+        })
+        // `max_by_key` returns the last maximum, so later registrations win
+        .max_by_key(|(_, ext)| ext.len())
+        .map(|(i, _)| i)
+}
+
+/// Runs `importer` on the file at `path`, using the options and state of its `.meta` file if there is one.
+///
+/// Unlike the daemon this never writes `.meta` files. Without one, the [`state`](ImportedSource::state) of the
+/// previous import of the file can be passed as `previous_state` to keep the UUIDs, otherwise the assets get new ones.
+///
+/// With a `uuid_root`, assets without a UUID in the importer state get the
+/// [`deterministic_asset_uuid`](crate::util::deterministic_asset_uuid) of their path relative to it.
+pub(crate) fn import_source(
+    importer: &dyn BoxedImporter,
+    path: &Path,
+    uuid_root: Option<&Path>,
+    previous_state: Option<&[u8]>,
+) -> Result<ImportedSource, ImportError> {
+    let meta_path = meta_path(path);
+    let (options, state) = match std::fs::read(&meta_path) {
+        Ok(bytes) => {
+            let metadata = importer
+                .deserialize_metadata(&bytes)
+                .map_err(|e| ImportError::Metadata(meta_path.clone(), e))?;
+            (metadata.importer_options, metadata.importer_state)
+        }
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            let default_state = importer.default_state();
+            // the previous state belongs to another importer if the file's importer changed
+            let state = previous_state
+                .and_then(|bytes| importer.deserialize_state(bytes).ok())
+                .filter(|state| state.type_uuid() == default_state.type_uuid())
+                .unwrap_or(default_state);
+            (importer.default_options(), state)
+        }
+        Err(e) => return Err(ImportError::Io(meta_path, e)),
+    };
+
+    let mut file = std::fs::File::open(path).map_err(|e| ImportError::Io(path.to_path_buf(), e))?;
+    let context = ImportSerdeContext::new();
+    let import = || {
+        context.run(|| importer.import_boxed(&mut ImportOp::default(), &mut file, options, state))
+    };
+    let imported = match uuid_root {
+        Some(root) => {
+            util::with_deterministic_uuids(path.strip_prefix(root).unwrap_or(path), import)
+        }
+        None => import(),
+    }
+    .map_err(|e| ImportError::Import(path.to_path_buf(), e))?;
+
+    let state = bincode::serialize(&imported.state)
+        .map_err(|e| ImportError::Serialize(path.to_path_buf(), e))?;
+
+    Ok(ImportedSource {
+        path: path.to_path_buf(),
+        assets: imported.value.assets,
+        state,
+        context,
+    })
+}
+
+/// The assets imported by [`import_source`], before they are serialized.
+///
+/// Assets can only be serialized once the UUIDs of the paths their handles point at are known,
+/// so sources referencing each other are imported first and serialized afterwards.
+pub(crate) struct ImportedSource {
+    path: PathBuf,
+    assets: Vec<ImportedAsset>,
+    /// The importer state after the import serialized like importers expect it in
+    /// [`deserialize_state`](BoxedImporter::deserialize_state), which keeps the UUIDs of the assets.
+    pub state: Vec<u8>,
+    // declared after `assets`, so the handles in them are dropped while the context still exists
+    context: ImportSerdeContext,
+}
+
+impl ImportedSource {
+    pub fn assets(&self) -> &[ImportedAsset] {
+        &self.assets
+    }
+
+    pub fn asset_ids(&self) -> impl Iterator<Item = AssetUuid> + '_ {
+        self.assets.iter().map(|asset| asset.id)
+    }
+
+    /// Serializes the assets, with handles to paths pointing at the asset `resolve_path` returns
+    /// for them, relative to `source` or to the asset root. `source` is relative to the asset root as well.
+    pub fn serialize(
+        &mut self,
+        source: &Path,
+        resolve_path: impl Fn(&Path) -> Option<AssetUuid>,
+    ) -> Result<Vec<ImportedArtifact>, ImportError> {
+        let paths: Vec<_> = self.context.handles.paths();
+        for path in paths {
+            match resolve_relative(source, &path, &resolve_path) {
+                Some(uuid) => self.context.handles.resolve_path(path, uuid),
+                None => bevy_log::warn!(
+                    "could not resolve handle to {} in {}",
+                    path.display(),
+                    self.path.display()
+                ),
+            }
+        }
+
+        let assets = std::mem::take(&mut self.assets);
+        assets
+            .into_iter()
+            .map(|asset| self.serialize_asset(asset))
+            .collect()
+    }
+
+    fn serialize_asset(&self, asset: ImportedAsset) -> Result<ImportedArtifact, ImportError> {
+        let data = self
+            .context
+            .run(|| bincode::serialize(&asset.asset_data))
+            .map_err(|e| ImportError::Serialize(self.path.clone(), e))?;
+        let type_id = AssetTypeId(*asset.asset_data.type_uuid().as_bytes());
+
+        // stays the same across platforms and Rust versions
+        let mut hasher = util::StableHasher::new();
+        hasher.write(&asset.id.0);
+        hasher.write(&data);
+        let artifact_id = ArtifactId(hasher.finish());
+
+        let artifact = ArtifactMetadata {
+            artifact_id,
+            asset_id: asset.id,
+            build_deps: asset.build_deps,
+            load_deps: asset.load_deps,
+            compression: CompressionType::None,
+            compressed_size: None,
+            uncompressed_size: Some(data.len() as u64),
+            type_id,
+        };
+
+        Ok(ImportedArtifact {
+            metadata: AssetMetadata {
+                id: asset.id,
+                search_tags: asset.search_tags,
+                build_pipeline: asset.build_pipeline,
+                artifact: Some(artifact),
+            },
+            data,
+        })
+    }
+}
+
+/// The [`SerdeContext`] importers and the serialization of imported assets run in,
+/// like the one the asset daemon provides, so that assets can contain handles.
+struct ImportSerdeContext {
+    handles: ImportHandles,
+    refop_sender: Sender<RefOp>,
+    // handles created while importing report their drop here
+    _refop_receiver: Receiver<RefOp>,
+}
+
+impl ImportSerdeContext {
+    fn new() -> Self {
+        let (refop_sender, refop_receiver) = unbounded();
+        ImportSerdeContext {
+            handles: ImportHandles::default(),
+            refop_sender,
+            _refop_receiver: refop_receiver,
+        }
+    }
+
+    fn run<R>(&self, f: impl FnOnce() -> R) -> R {
+        futures_executor::block_on(SerdeContext::with(
+            &self.handles,
+            self.refop_sender.clone(),
+            async { f() },
+        ))
+    }
+}
+
+/// Hands out load handles for the handles created while importing a source,
+/// and turns them back into UUIDs when the imported assets are serialized.
+#[derive(Default)]
+struct ImportHandles {
+    /// The asset referenced by load handle `i + 1`.
+    refs: RwLock<Vec<AssetRef>>,
+    resolved_paths: RwLock<HashMap<PathBuf, AssetUuid>>,
+}
+
+impl ImportHandles {
+    fn paths(&self) -> Vec<PathBuf> {
+        let refs = self.refs.read().unwrap();
+        refs.iter()
+            .filter_map(|asset_ref| match asset_ref {
+                AssetRef::Path(path) => Some(path.clone()),
+                AssetRef::Uuid(_) => None,
+            })
+            .collect()
+    }
+
+    fn resolve_path(&self, path: PathBuf, uuid: AssetUuid) {
+        self.resolved_paths.write().unwrap().insert(path, uuid);
+    }
+}
+
+impl LoaderInfoProvider for ImportHandles {
+    fn get_load_handle(&self, id: &AssetRef) -> Option<LoadHandle> {
+        let mut refs = self.refs.write().unwrap();
+        let index = match refs.iter().position(|other| other == id) {
+            Some(index) => index,
+            None => {
+                refs.push(id.clone());
+                refs.len() - 1
+            }
+        };
+        Some(LoadHandle(index as u64 + 1))
+    }
+
+    fn get_asset_id(&self, load: LoadHandle) -> Option<AssetUuid> {
+        let refs = self.refs.read().unwrap();
+        match refs.get((load.0 as usize).checked_sub(1)?)? {
+            AssetRef::Uuid(id) => Some(*id),
+            AssetRef::Path(path) => self.resolved_paths.read().unwrap().get(path).copied(),
+        }
+    }
+}
+
+/// Resolves `path` relative to the importing file at `source`, falling back to the asset root.
+fn resolve_relative(
+    source: &Path,
+    path: &Path,
+    resolve_path: impl Fn(&Path) -> Option<AssetUuid>,
+) -> Option<AssetUuid> {
+    let relative = source.parent().map(|parent| parent.join(path));
+    relative
+        .as_deref()
+        .and_then(&resolve_path)
+        .or_else(|| resolve_path(path))
+}
+
+/// Resolves path references in `load_deps` relative to the importing file, falling back to the asset root.
+pub(crate) fn resolve_load_deps(
+    artifact: &ArtifactMetadata,
+    source: &Path,
+    resolve_path: impl Fn(&Path) -> Option<AssetUuid>,
+) -> ArtifactMetadata {
+    let mut artifact = artifact.clone();
+    for dep in &mut artifact.load_deps {
+        if let AssetRef::Path(path) = dep {
+            match resolve_relative(source, path, &resolve_path) {
+                Some(uuid) => *dep = AssetRef::Uuid(uuid),
+                None => bevy_log::warn!(
+                    "could not resolve dependency {} of {}",
+                    path.display(),
+                    source.display()
+                ),
+            }
+        }
+    }
+    artifact
+}
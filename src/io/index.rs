@@ -0,0 +1,189 @@
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock, Weak};
+use std::time::{Duration, SystemTime};
+
+use bevy_utils::HashMap;
+use distill_core::AssetUuid;
+
+use super::import;
+use super::layered::LayerIndex;
+
+const RESCAN_INTERVAL: Duration = Duration::from_secs(1);
+
+struct IndexedSource {
+    modified: Option<SystemTime>,
+    assets: Vec<AssetUuid>,
+}
+
+/// The sources found by the last scan, by their path relative to their asset dir.
+#[derive(Default)]
+struct MetaIndexContents {
+    sources: HashMap<PathBuf, IndexedSource>,
+    assets: HashSet<AssetUuid>,
+}
+
+/// Indexes the asset directories of a daemon by their `.meta` files.
+///
+/// The daemon doesn't tell which assets it has, but it writes the state of every import to
+/// the `.meta` file of the source, which holds the UUIDs of its assets, including the ones of
+/// labelled sub-assets. Sources the daemon hasn't imported yet are added once it wrote their `.meta` file.
+pub(crate) struct MetaIndex(RwLock<MetaIndexContents>);
+
+impl MetaIndex {
+    /// Creates the index and keeps it up to date on a background thread for as long as it is alive.
+    ///
+    /// The index starts out empty, the first scan also runs on the background thread.
+    pub fn watch(asset_dirs: Vec<PathBuf>) -> Arc<MetaIndex> {
+        let index = Arc::new(MetaIndex(RwLock::default()));
+
+        let weak = Arc::downgrade(&index);
+        std::thread::Builder::new()
+            .name("asset meta index".to_string())
+            .spawn(move || rescan_loop(asset_dirs, weak))
+            .expect("failed to spawn asset meta index thread");
+
+        index
+    }
+
+    /// Reads the `.meta` files that changed since the last scan.
+    fn rescan(&self, asset_dirs: &[PathBuf]) {
+        let mut sources = HashMap::default();
+        for asset_dir in asset_dirs {
+            let mut files = Vec::new();
+            if import::collect_sources(asset_dir, &mut files).is_err() {
+                continue;
+            }
+            for path in files {
+                let meta_path = import::meta_path(&path);
+                let modified = match std::fs::metadata(&meta_path).and_then(|m| m.modified()) {
+                    Ok(modified) => Some(modified),
+                    // not imported by the daemon (yet)
+                    Err(_) if !meta_path.exists() => continue,
+                    Err(_) => None,
+                };
+                let relative = path.strip_prefix(asset_dir).unwrap_or(&path).to_path_buf();
+
+                let unchanged = self
+                    .0
+                    .read()
+                    .unwrap()
+                    .sources
+                    .get(&relative)
+                    .filter(|source| modified.is_some() && source.modified == modified)
+                    .map(|source| source.assets.clone());
+                let assets = match unchanged {
+                    Some(assets) => assets,
+                    None => match read_asset_ids(&meta_path) {
+                        Ok(assets) => assets,
+                        Err(e) => {
+                            bevy_log::debug!("failed to index {}: {}", meta_path.display(), e);
+                            Vec::new()
+                        }
+                    },
+                };
+                sources.insert(relative, IndexedSource { modified, assets });
+            }
+        }
+
+        let assets = sources
+            .values()
+            .flat_map(|source| source.assets.iter().copied())
+            .collect();
+        *self.0.write().unwrap() = MetaIndexContents { sources, assets };
+    }
+}
+
+impl LayerIndex for MetaIndex {
+    fn contains_asset(&self, id: &AssetUuid) -> bool {
+        self.0.read().unwrap().assets.contains(id)
+    }
+
+    fn contains_path(&self, path: &Path) -> bool {
+        self.0.read().unwrap().sources.contains_key(path)
+    }
+}
+
+fn rescan_loop(asset_dirs: Vec<PathBuf>, index: Weak<MetaIndex>) {
+    loop {
+        match index.upgrade() {
+            Some(index) => index.rescan(&asset_dirs),
+            None => break,
+        }
+        std::thread::sleep(RESCAN_INTERVAL);
+    }
+}
+
+/// The UUIDs in the importer state of a `.meta` file.
+///
+/// The layout of the state depends on the importer, but every importer stores the UUIDs
+/// of its assets in it, as strings.
+fn read_asset_ids(meta_path: &Path) -> Result<Vec<AssetUuid>, Box<dyn std::error::Error>> {
+    let contents = std::fs::read_to_string(meta_path)?;
+    let metadata: ron::Value = ron::de::from_str(&contents)?;
+    let state = match metadata {
+        ron::Value::Map(fields) => fields
+            .iter()
+            .find(|(key, _)| **key == ron::Value::String("importer_state".to_string()))
+            .map(|(_, state)| state.clone()),
+        _ => None,
+    }
+    .ok_or("no `importer_state`")?;
+
+    let mut assets = Vec::new();
+    collect_uuids(&state, &mut assets);
+    Ok(assets)
+}
+
+fn collect_uuids(value: &ron::Value, uuids: &mut Vec<AssetUuid>) {
+    match value {
+        ron::Value::String(s) => {
+            if let Ok(uuid) = uuid::Uuid::parse_str(s) {
+                uuids.push(AssetUuid(*uuid.as_bytes()));
+            }
+        }
+        ron::Value::Option(Some(value)) => collect_uuids(value, uuids),
+        ron::Value::Seq(values) => {
+            for value in values {
+                collect_uuids(value, uuids);
+            }
+        }
+        // only the values, label names are no assets
+        ron::Value::Map(map) => {
+            for (_, value) in map.iter() {
+                collect_uuids(value, uuids);
+            }
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_asset_ids_from_meta_files() {
+        let ids = |path: &str| -> Vec<String> {
+            read_asset_ids(Path::new(path))
+                .unwrap()
+                .into_iter()
+                .map(|id| uuid::Uuid::from_bytes(id.0).to_string())
+                .collect()
+        };
+
+        assert_eq!(
+            ids("assets/custom_asset.casset.meta"),
+            ["d1159d76-813b-4900-a344-c2568070abc0"]
+        );
+        let mut scene = ids("assets/a.scene.ron.meta");
+        scene.sort();
+        assert_eq!(
+            scene,
+            [
+                "40490790-496c-4675-96ca-b8eb6ee048f0",
+                "85ed492d-a4b0-471a-a56f-0246f18cc645"
+            ]
+        );
+    }
+}
@@ -0,0 +1,290 @@
+use std::collections::HashSet;
+use std::path::Path;
+use std::sync::Arc;
+
+#[cfg(feature = "packfile")]
+use bevy_utils::HashMap;
+use distill_core::{ArtifactMetadata, AssetRef, AssetUuid};
+#[cfg(feature = "packfile")]
+use distill_loader::crossbeam_channel::{Receiver, Sender};
+use distill_loader::io::{DataRequest, LoaderIO, MetadataRequest, ResolveRequest};
+use distill_loader::loader::LoaderState;
+use distill_loader::storage::IndirectIdentifier;
+
+use super::identifier_path;
+
+#[cfg(feature = "packfile")]
+use super::packfile::{PackfileError, PackfileId, PackfileIndex};
+
+/// Answers which assets a layer of a [`LayeredIO`] provides.
+pub(crate) trait LayerIndex: Send + Sync {
+    fn contains_asset(&self, id: &AssetUuid) -> bool;
+    fn contains_path(&self, path: &Path) -> bool;
+    /// The metadata of an asset in the layer, or `None` if the layer only provides it
+    /// asynchronously through its [`LoaderIO`].
+    fn artifact_metadata(&self, _id: &AssetUuid) -> Option<ArtifactMetadata> {
+        None
+    }
+}
+
+pub(crate) struct Layer {
+    pub io: Box<dyn LoaderIO>,
+    /// `None` for layers that can't tell what they contain. Those can only be used as the base layer.
+    pub index: Option<Arc<dyn LayerIndex>>,
+}
+
+/// A [`LoaderIO`] that serves every request from the highest-priority layer that has the asset,
+/// falling back to the base layer.
+///
+/// Layers are ordered by priority, the first one is the base layer.
+/// Metadata is requested in batches which can't be split, so a batch of assets from different layers
+/// is answered from the [`LayerIndex`]es of their layers. That isn't possible if one of the layers
+/// only knows the metadata asynchronously, like a daemon, in which case the batch goes to that layer.
+/// Data and path requests are routed individually.
+pub(crate) struct LayeredIO {
+    layers: Vec<Layer>,
+    #[cfg(feature = "packfile")]
+    mounts: Option<Mounts>,
+}
+
+/// Sent by the [`AssetServer`](crate::AssetServer) to add or remove packfiles while running.
+#[cfg(feature = "packfile")]
+pub(crate) enum MountCommand {
+    /// The packfile opened on the IO task pool.
+    Mount(
+        PackfileId,
+        Result<(Layer, Arc<PackfileIndex>), PackfileError>,
+    ),
+    Unmount(PackfileId),
+}
+
+#[cfg(feature = "packfile")]
+struct Mounts {
+    commands: Receiver<MountCommand>,
+    /// Receives the assets that no layer provides anymore after an unmount.
+    removed: Sender<Vec<AssetUuid>>,
+    /// The mounted packfiles and the index of their layer.
+    mounted: HashMap<PackfileId, (usize, Arc<PackfileIndex>)>,
+    /// Packfiles unmounted before they were opened.
+    cancelled: HashSet<PackfileId>,
+}
+
+impl LayeredIO {
+    pub fn new(layers: Vec<Layer>) -> Self {
+        assert!(!layers.is_empty(), "layered asset source without layers");
+        LayeredIO {
+            layers,
+            #[cfg(feature = "packfile")]
+            mounts: None,
+        }
+    }
+
+    #[cfg(feature = "packfile")]
+    pub fn with_mounts(
+        mut self,
+        commands: Receiver<MountCommand>,
+        removed: Sender<Vec<AssetUuid>>,
+    ) -> Self {
+        self.mounts = Some(Mounts {
+            commands,
+            removed,
+            mounted: HashMap::default(),
+            cancelled: HashSet::new(),
+        });
+        self
+    }
+
+    #[cfg(feature = "packfile")]
+    fn process_mounts(&mut self, loader: &mut LoaderState) {
+        let commands: Vec<_> = match &self.mounts {
+            Some(mounts) => mounts.commands.try_iter().collect(),
+            None => return,
+        };
+
+        for command in commands {
+            let (index, removed) = match command {
+                MountCommand::Mount(id, result) => {
+                    let mounts = self.mounts.as_mut().unwrap();
+                    if mounts.cancelled.remove(&id) {
+                        continue;
+                    }
+                    let (layer, index) = match result {
+                        Ok(mounted) => mounted,
+                        Err(e) => {
+                            bevy_log::error!("failed to mount packfile {:?}: {}", id, e);
+                            continue;
+                        }
+                    };
+                    bevy_log::debug!("mounted packfile {:?}", id);
+                    mounts
+                        .mounted
+                        .insert(id, (self.layers.len(), Arc::clone(&index)));
+                    self.layers.push(layer);
+                    (index, Vec::new())
+                }
+                MountCommand::Unmount(id) => {
+                    let mounts = self.mounts.as_mut().unwrap();
+                    let (position, index) = match mounts.mounted.remove(&id) {
+                        Some(mounted) => mounted,
+                        // still being opened
+                        None => {
+                            mounts.cancelled.insert(id);
+                            continue;
+                        }
+                    };
+                    for (other_position, _) in mounts.mounted.values_mut() {
+                        if *other_position > position {
+                            *other_position -= 1;
+                        }
+                    }
+                    self.layers.remove(position);
+                    bevy_log::debug!("unmounted packfile {:?}", id);
+
+                    // a base layer without an index might still have the asset
+                    let removed = match self.layers[0].index {
+                        Some(_) => index
+                            .assets()
+                            .filter(|id| {
+                                !self
+                                    .layers
+                                    .iter()
+                                    .filter_map(|layer| layer.index.as_deref())
+                                    .any(|layer| layer.contains_asset(id))
+                            })
+                            .copied()
+                            .collect(),
+                        None => Vec::new(),
+                    };
+                    (index, removed)
+                }
+            };
+
+            // assets and paths of the packfile now resolve to a different layer
+            let assets: Vec<_> = index.assets().copied().collect();
+            let paths: Vec<_> = index.paths().cloned().collect();
+            loader.invalidate_assets(&assets);
+            loader.invalidate_paths(&paths);
+
+            if !removed.is_empty() {
+                let _ = self.mounts.as_ref().unwrap().removed.send(removed);
+            }
+        }
+    }
+
+    fn upper_layers(&self) -> impl Iterator<Item = (usize, &dyn LayerIndex)> {
+        self.layers
+            .iter()
+            .enumerate()
+            .skip(1)
+            .rev()
+            .filter_map(|(i, layer)| layer.index.as_deref().map(|index| (i, index)))
+    }
+
+    fn layer_for_asset(&self, id: &AssetUuid) -> usize {
+        self.upper_layers()
+            .find(|(_, index)| index.contains_asset(id))
+            .map_or(0, |(i, _)| i)
+    }
+
+    /// The metadata of `ids` and their dependencies, each from the layer that provides it.
+    ///
+    /// Returns the first layer whose index can't answer as the error.
+    fn merged_metadata<'a>(
+        &self,
+        ids: impl Iterator<Item = &'a AssetUuid>,
+    ) -> Result<Vec<ArtifactMetadata>, usize> {
+        let mut visited = HashSet::new();
+        let mut stack: Vec<AssetUuid> = ids.copied().collect();
+        let mut artifacts = Vec::new();
+        while let Some(id) = stack.pop() {
+            if !visited.insert(id) {
+                continue;
+            }
+            let layer = self.layer_for_asset(&id);
+            let index = match self.layers[layer].index.as_deref() {
+                Some(index) => index,
+                None => return Err(layer),
+            };
+            let artifact = match index.artifact_metadata(&id) {
+                Some(artifact) => artifact,
+                // missing assets are left out, like every layer does on its own
+                None if !index.contains_asset(&id) => continue,
+                None => return Err(layer),
+            };
+            stack.extend(artifact.load_deps.iter().filter_map(|dep| match dep {
+                AssetRef::Uuid(uuid) => Some(*uuid),
+                AssetRef::Path(_) => None,
+            }));
+            artifacts.push(artifact);
+        }
+        Ok(artifacts)
+    }
+
+    fn layer_for_identifier(&self, identifier: &IndirectIdentifier) -> usize {
+        let path = Path::new(identifier_path(identifier));
+        self.upper_layers()
+            .find(|(_, index)| index.contains_path(path))
+            .map_or(0, |(i, _)| i)
+    }
+}
+
+impl LoaderIO for LayeredIO {
+    fn get_asset_metadata_with_dependencies(&mut self, request: MetadataRequest) {
+        let layers: HashSet<usize> = request
+            .requested_assets()
+            .map(|id| self.layer_for_asset(id))
+            .collect();
+        let layer = match layers.len() {
+            0 | 1 => layers.into_iter().next().unwrap_or(0),
+            _ => match self.merged_metadata(request.requested_assets()) {
+                Ok(artifacts) => return request.complete(artifacts),
+                Err(layer) => {
+                    bevy_log::debug!(
+                        "metadata request spans multiple layers, but layer {} can only answer it itself",
+                        layer
+                    );
+                    layer
+                }
+            },
+        };
+        self.layers[layer]
+            .io
+            .get_asset_metadata_with_dependencies(request);
+    }
+
+    fn get_asset_candidates(&mut self, requests: Vec<ResolveRequest>) {
+        let mut per_layer: Vec<Vec<ResolveRequest>> =
+            self.layers.iter().map(|_| Vec::new()).collect();
+        for request in requests {
+            per_layer[self.layer_for_identifier(request.identifier())].push(request);
+        }
+        for (layer, requests) in self.layers.iter_mut().zip(per_layer) {
+            if !requests.is_empty() {
+                layer.io.get_asset_candidates(requests);
+            }
+        }
+    }
+
+    fn get_artifacts(&mut self, requests: Vec<DataRequest>) {
+        let mut per_layer: Vec<Vec<DataRequest>> = self.layers.iter().map(|_| Vec::new()).collect();
+        for request in requests {
+            per_layer[self.layer_for_asset(&request.asset_id())].push(request);
+        }
+        for (layer, requests) in self.layers.iter_mut().zip(per_layer) {
+            if !requests.is_empty() {
+                layer.io.get_artifacts(requests);
+            }
+        }
+    }
+
+    fn tick(&mut self, loader: &mut LoaderState) {
+        #[cfg(feature = "packfile")]
+        self.process_mounts(loader);
+
+        // every layer invalidates the assets that changed in it, which makes the loader request
+        // them again, so changes in an upper layer reload the asset they shadow
+        for layer in &mut self.layers {
+            layer.io.tick(loader);
+        }
+    }
+}
@@ -0,0 +1,386 @@
+use std::collections::hash_map::DefaultHasher;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use bevy_utils::HashMap;
+use distill_core::{
+    ArtifactId, ArtifactMetadata, AssetMetadata, AssetTypeId, AssetUuid, CompressionType,
+};
+use distill_loader::io::{DataRequest, LoaderIO, MetadataRequest, ResolveRequest};
+use distill_loader::loader::LoaderState;
+use serde::Serialize;
+
+use super::identifier_path;
+use super::layered::LayerIndex;
+use crate::Asset;
+
+#[derive(Debug)]
+pub(crate) enum MemoryIOError {
+    AssetNotFound(AssetUuid),
+    PathNotFound(String),
+    Injected(String),
+}
+
+impl fmt::Display for MemoryIOError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MemoryIOError::AssetNotFound(id) => write!(f, "no asset with id {:?}", id),
+            MemoryIOError::PathNotFound(path) => write!(f, "no asset at path {}", path),
+            MemoryIOError::Injected(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl std::error::Error for MemoryIOError {}
+
+struct MemoryAsset {
+    path: Option<PathBuf>,
+    artifact: ArtifactMetadata,
+    data: Vec<u8>,
+    failure: Option<String>,
+}
+
+impl MemoryAsset {
+    fn metadata(&self) -> AssetMetadata {
+        AssetMetadata {
+            id: self.artifact.asset_id,
+            search_tags: Vec::new(),
+            build_pipeline: None,
+            artifact: Some(self.artifact.clone()),
+        }
+    }
+}
+
+#[derive(Default)]
+pub(crate) struct MemoryContents {
+    assets: HashMap<AssetUuid, MemoryAsset>,
+    changed_assets: Vec<AssetUuid>,
+    changed_paths: Vec<PathBuf>,
+}
+
+impl MemoryContents {
+    fn mark_changed(&mut self, id: AssetUuid, path: Option<PathBuf>) {
+        self.changed_assets.push(id);
+        self.changed_paths.extend(path);
+    }
+}
+
+impl LayerIndex for Mutex<MemoryContents> {
+    fn contains_asset(&self, id: &AssetUuid) -> bool {
+        self.lock().unwrap().assets.contains_key(id)
+    }
+
+    fn contains_path(&self, path: &Path) -> bool {
+        let contents = self.lock().unwrap();
+        contents
+            .assets
+            .values()
+            .any(|asset| asset.path.as_deref() == Some(path))
+    }
+
+    fn artifact_metadata(&self, id: &AssetUuid) -> Option<ArtifactMetadata> {
+        let contents = self.lock().unwrap();
+        contents.assets.get(id).map(|asset| asset.artifact.clone())
+    }
+}
+
+/// Assets that are kept in memory instead of being imported from files,
+/// for testing systems that load assets without a daemon or packfile.
+///
+/// The source can be changed after the [`AssetPlugin`](crate::AssetPlugin) was built:
+/// assets inserted again are reloaded like on a hot reload, and [`fail`](Self::fail)
+/// makes loads of an asset fail. Keep a clone of the source around to do that.
+#[derive(Clone, Default)]
+pub struct MemoryAssetSource(Arc<Mutex<MemoryContents>>);
+
+impl fmt::Debug for MemoryAssetSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let contents = self.0.lock().unwrap();
+        f.debug_struct("MemoryAssetSource")
+            .field("assets", &contents.assets.len())
+            .finish()
+    }
+}
+
+impl MemoryAssetSource {
+    pub fn new() -> Self {
+        MemoryAssetSource::default()
+    }
+
+    /// Inserts or replaces the asset `uuid`, which can also be loaded by `path`.
+    ///
+    /// The value is serialized the same way importers serialize assets, so it is loaded
+    /// with the deserializer registered by [`AddAsset`](crate::AddAsset).
+    /// It has no schema version, so for asset types with an [`AssetSchema`](crate::schema::AssetSchema)
+    /// use [`insert_raw`](Self::insert_raw) with data from [`schema::serialize`](crate::schema::serialize).
+    pub fn insert_serialized<A: Asset + Serialize>(&self, uuid: AssetUuid, path: &str, value: &A) {
+        let data = bincode::serialize(value).expect("failed to serialize in-memory asset");
+        self.insert_raw(
+            uuid,
+            Some(PathBuf::from(path)),
+            AssetTypeId(*A::TYPE_UUID.as_bytes()),
+            data,
+        );
+    }
+
+    /// Inserts or replaces the asset `uuid` with already serialized data of type `asset_type`.
+    pub fn insert_raw(
+        &self,
+        uuid: AssetUuid,
+        path: Option<PathBuf>,
+        asset_type: AssetTypeId,
+        data: Vec<u8>,
+    ) {
+        let mut hasher = DefaultHasher::new();
+        uuid.hash(&mut hasher);
+        data.hash(&mut hasher);
+
+        let artifact = ArtifactMetadata {
+            artifact_id: ArtifactId(hasher.finish()),
+            asset_id: uuid,
+            build_deps: Vec::new(),
+            load_deps: Vec::new(),
+            compression: CompressionType::None,
+            compressed_size: None,
+            uncompressed_size: Some(data.len() as u64),
+            type_id: asset_type,
+        };
+
+        let mut contents = self.0.lock().unwrap();
+        let old_path = contents.assets.get(&uuid).and_then(|old| old.path.clone());
+        contents.assets.insert(
+            uuid,
+            MemoryAsset {
+                path: path.clone(),
+                artifact,
+                data,
+                failure: None,
+            },
+        );
+        if old_path != path {
+            contents.changed_paths.extend(old_path);
+        }
+        contents.mark_changed(uuid, path);
+    }
+
+    /// Removes the asset, so that loading it fails.
+    pub fn remove(&self, uuid: AssetUuid) {
+        let mut contents = self.0.lock().unwrap();
+        if let Some(asset) = contents.assets.remove(&uuid) {
+            contents.mark_changed(uuid, asset.path);
+        }
+    }
+
+    /// Makes loading the asset fail with `message` until it is inserted again or [`recover`](Self::recover) is called.
+    ///
+    /// Already loaded versions of the asset are reloaded, so the failure is also visible for hot reloads.
+    pub fn fail(&self, uuid: AssetUuid, message: impl Into<String>) {
+        self.set_failure(uuid, Some(message.into()));
+    }
+
+    /// Reverts [`fail`](Self::fail).
+    pub fn recover(&self, uuid: AssetUuid) {
+        self.set_failure(uuid, None);
+    }
+
+    fn set_failure(&self, uuid: AssetUuid, failure: Option<String>) {
+        let mut contents = self.0.lock().unwrap();
+        let path = match contents.assets.get_mut(&uuid) {
+            Some(asset) => {
+                asset.failure = failure;
+                asset.path.clone()
+            }
+            None => {
+                bevy_log::warn!("no in-memory asset with id {:?}", uuid);
+                return;
+            }
+        };
+        contents.mark_changed(uuid, path);
+    }
+
+    pub(crate) fn contents(&self) -> Arc<Mutex<MemoryContents>> {
+        Arc::clone(&self.0)
+    }
+}
+
+/// A [`LoaderIO`] serving the assets of a [`MemoryAssetSource`].
+pub(crate) struct MemoryIO {
+    source: MemoryAssetSource,
+}
+
+impl MemoryIO {
+    pub fn new(source: MemoryAssetSource) -> Self {
+        MemoryIO { source }
+    }
+}
+
+impl LoaderIO for MemoryIO {
+    fn get_asset_metadata_with_dependencies(&mut self, request: MetadataRequest) {
+        let contents = self.source.0.lock().unwrap();
+        let artifacts = request
+            .requested_assets()
+            .filter_map(|id| contents.assets.get(id))
+            .map(|asset| asset.artifact.clone())
+            .collect();
+        request.complete(artifacts);
+    }
+
+    fn get_asset_candidates(&mut self, requests: Vec<ResolveRequest>) {
+        let contents = self.source.0.lock().unwrap();
+        for request in requests {
+            let path = identifier_path(request.identifier()).to_string();
+            let metadata: Vec<_> = contents
+                .assets
+                .values()
+                .filter(|asset| asset.path.as_deref() == Some(Path::new(&path)))
+                .map(MemoryAsset::metadata)
+                .collect();
+            if metadata.is_empty() {
+                request.error(MemoryIOError::PathNotFound(path));
+            } else {
+                request.complete(vec![(PathBuf::from(path), metadata)]);
+            }
+        }
+    }
+
+    fn get_artifacts(&mut self, requests: Vec<DataRequest>) {
+        let contents = self.source.0.lock().unwrap();
+        for request in requests {
+            let id = request.asset_id();
+            match contents.assets.get(&id) {
+                Some(MemoryAsset {
+                    failure: Some(message),
+                    ..
+                }) => request.error(MemoryIOError::Injected(message.clone())),
+                Some(asset) => request.complete(asset.data.clone()),
+                None => request.error(MemoryIOError::AssetNotFound(id)),
+            }
+        }
+    }
+
+    fn tick(&mut self, loader: &mut LoaderState) {
+        let (assets, paths) = {
+            let mut contents = self.source.0.lock().unwrap();
+            (
+                std::mem::take(&mut contents.changed_assets),
+                std::mem::take(&mut contents.changed_paths),
+            )
+        };
+        if !assets.is_empty() {
+            loader.invalidate_assets(&assets);
+        }
+        if !paths.is_empty() {
+            loader.invalidate_paths(&paths);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy_app::App;
+    use bevy_ecs::world::World;
+    use distill_loader::storage::LoadStatus;
+
+    use super::*;
+    use crate::plugin::in_memory_app;
+    use crate::prelude::*;
+
+    #[derive(Serialize, Deserialize, TypeUuid)]
+    #[uuid = "6a1b5c0e-2f43-4d7a-9c8e-1b2d3e4f5a60"]
+    struct TestAsset {
+        value: String,
+    }
+
+    const ID: AssetUuid = AssetUuid([7; 16]);
+
+    fn insert(source: &MemoryAssetSource, value: &str) {
+        let value = value.to_string();
+        source.insert_serialized(ID, "test.asset", &TestAsset { value });
+    }
+
+    fn load(source: &MemoryAssetSource) -> (App, Handle<TestAsset>) {
+        let mut app = in_memory_app(source.clone());
+        app.add_asset::<TestAsset>();
+        let handle = app
+            .world
+            .get_resource::<AssetServer>()
+            .unwrap()
+            .load("test.asset");
+        (app, handle)
+    }
+
+    /// Runs frames until `done`, the loader needs a few of them for every step.
+    fn update_until(app: &mut App, mut done: impl FnMut(&World) -> bool) {
+        for _ in 0..1000 {
+            app.update();
+            if done(&app.world) {
+                return;
+            }
+            // deserialization runs on the task pool
+            std::thread::sleep(std::time::Duration::from_millis(1));
+        }
+        panic!("condition not reached after 1000 frames");
+    }
+
+    fn value(world: &World, handle: &Handle<TestAsset>) -> Option<String> {
+        let assets = world.get_resource::<Assets<TestAsset>>().unwrap();
+        assets.get(handle).map(|asset| asset.value.clone())
+    }
+
+    fn status(world: &World, handle: &Handle<TestAsset>) -> LoadStatus {
+        let asset_server = world.get_resource::<AssetServer>().unwrap();
+        asset_server.get_load_status(handle)
+    }
+
+    #[test]
+    fn loads_and_hot_reloads() {
+        let source = MemoryAssetSource::new();
+        insert(&source, "first");
+        let (mut app, handle) = load(&source);
+
+        update_until(&mut app, |world| value(world, &handle).is_some());
+        assert_eq!(value(&app.world, &handle).unwrap(), "first");
+        assert!(matches!(status(&app.world, &handle), LoadStatus::Loaded));
+
+        insert(&source, "second");
+        update_until(&mut app, |world| {
+            value(world, &handle).as_deref() == Some("second")
+        });
+    }
+
+    #[test]
+    fn removed_asset_fails_to_load() {
+        let source = MemoryAssetSource::new();
+        insert(&source, "first");
+        let (mut app, handle) = load(&source);
+        update_until(&mut app, |world| value(world, &handle).is_some());
+
+        source.remove(ID);
+        update_until(&mut app, |world| {
+            matches!(
+                status(world, &handle),
+                LoadStatus::DoesNotExist | LoadStatus::Error(_)
+            )
+        });
+    }
+
+    #[test]
+    fn failed_load_recovers() {
+        let source = MemoryAssetSource::new();
+        insert(&source, "first");
+        source.fail(ID, "broken");
+        let (mut app, handle) = load(&source);
+
+        update_until(&mut app, |world| {
+            matches!(status(world, &handle), LoadStatus::Error(_))
+        });
+        assert!(value(&app.world, &handle).is_none());
+
+        source.recover(ID);
+        update_until(&mut app, |world| value(world, &handle).is_some());
+        assert_eq!(value(&app.world, &handle).unwrap(), "first");
+        assert!(matches!(status(&app.world, &handle), LoadStatus::Loaded));
+    }
+}
@@ -0,0 +1,70 @@
+//! [`LoaderIO`](distill_loader::io::LoaderIO) implementations used by the [`AssetServer`](crate::AssetServer).
+
+#[cfg(not(target_family = "wasm"))]
+mod directory;
+pub(crate) mod import;
+#[cfg(feature = "asset-daemon")]
+mod index;
+mod layered;
+mod memory;
+#[cfg(feature = "packfile")]
+mod packfile;
+#[cfg(all(feature = "rpc-io", not(target_family = "wasm")))]
+mod reconnect;
+
+#[cfg(not(target_family = "wasm"))]
+pub(crate) use directory::DirectoryIO;
+#[cfg(feature = "asset-daemon")]
+pub(crate) use index::MetaIndex;
+#[cfg(feature = "packfile")]
+pub(crate) use layered::MountCommand;
+pub(crate) use layered::{Layer, LayerIndex, LayeredIO};
+pub use memory::MemoryAssetSource;
+pub(crate) use memory::MemoryIO;
+#[cfg(feature = "packfile")]
+pub(crate) use packfile::packfile_layer;
+#[cfg(feature = "packfile")]
+pub use packfile::{PackfileError, PackfileId};
+
+#[cfg(all(feature = "rpc-io", not(target_family = "wasm")))]
+pub(crate) use reconnect::{ReconnectingIO, RpcConnection};
+
+use distill_loader::storage::IndirectIdentifier;
+
+/// The path of an indirect load, without its label and asset type.
+pub(crate) fn identifier_path(identifier: &IndirectIdentifier) -> &str {
+    match identifier {
+        IndirectIdentifier::Path(path)
+        | IndirectIdentifier::PathWithType(path, _)
+        | IndirectIdentifier::PathWithTagAndType(path, _, _) => path,
+    }
+}
+
+/// The state of the connection to the asset daemon.
+///
+/// Sources that don't need a connection, like packfiles, are always [`Connected`](AssetConnectionStatus::Connected).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AssetConnectionStatus {
+    /// No connection has been established yet.
+    Connecting,
+    Connected,
+    /// The connection was lost and is retried after `attempts` failed reconnects.
+    Disconnected {
+        attempts: u32,
+    },
+}
+
+impl Default for AssetConnectionStatus {
+    fn default() -> Self {
+        AssetConnectionStatus::Connected
+    }
+}
+
+/// Sent whenever the [`AssetConnectionStatus`] changes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AssetConnectionEvent {
+    Connected,
+    Disconnected,
+    /// The connection to the daemon was restored after it had been lost.
+    Reconnected,
+}
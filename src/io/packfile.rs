@@ -0,0 +1,148 @@
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use bevy_utils::{HashMap, HashSet};
+use distill_core::{ArtifactMetadata, AssetUuid};
+use distill_loader::io::LoaderIO;
+use distill_loader::PackfileReader;
+
+use super::layered::{Layer, LayerIndex};
+use crate::settings::PackfileSettings;
+
+/// Identifies a packfile mounted with [`AssetServer::mount_packfile`](crate::AssetServer::mount_packfile).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PackfileId(pub(crate) u32);
+
+#[derive(Debug)]
+pub enum PackfileError {
+    Io(std::io::Error),
+    Capnp(capnp::Error),
+    NotInSchema(capnp::NotInSchema),
+    Reader(Box<dyn std::error::Error + Send + Sync>),
+}
+
+impl fmt::Display for PackfileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PackfileError::Io(e) => write!(f, "failed to read packfile: {}", e),
+            PackfileError::Capnp(e) => write!(f, "invalid packfile: {}", e),
+            PackfileError::NotInSchema(e) => write!(f, "invalid packfile: {}", e),
+            PackfileError::Reader(e) => write!(f, "failed to open packfile: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for PackfileError {}
+
+impl From<std::io::Error> for PackfileError {
+    fn from(e: std::io::Error) -> Self {
+        PackfileError::Io(e)
+    }
+}
+impl From<capnp::Error> for PackfileError {
+    fn from(e: capnp::Error) -> Self {
+        PackfileError::Capnp(e)
+    }
+}
+impl From<capnp::NotInSchema> for PackfileError {
+    fn from(e: capnp::NotInSchema) -> Self {
+        PackfileError::NotInSchema(e)
+    }
+}
+
+/// The assets and paths contained in a packfile.
+pub(crate) struct PackfileIndex {
+    assets: HashMap<AssetUuid, ArtifactMetadata>,
+    paths: HashSet<PathBuf>,
+}
+
+impl PackfileIndex {
+    pub fn read(mut bytes: &[u8]) -> Result<PackfileIndex, PackfileError> {
+        let message = capnp::serialize::read_message_from_flat_slice(
+            &mut bytes,
+            capnp::message::ReaderOptions {
+                traversal_limit_in_words: None,
+                nesting_limit: 64,
+            },
+        )?;
+        let packfile = message.get_root::<distill_schema::pack::pack_file::Reader<'_>>()?;
+
+        let mut index = PackfileIndex {
+            assets: HashMap::default(),
+            paths: HashSet::default(),
+        };
+        for entry in packfile.get_entries()?.iter() {
+            let mut id = AssetUuid::default();
+            id.0.copy_from_slice(entry.get_asset_metadata()?.get_id()?.get_id()?);
+            let artifact = entry.get_artifact()?.get_metadata()?;
+            index
+                .assets
+                .insert(id, distill_schema::parse_artifact_metadata(&artifact));
+
+            let path = std::str::from_utf8(entry.get_path()?)
+                .map_err(|e| capnp::Error::failed(e.to_string()))?;
+            if !path.is_empty() {
+                index.paths.insert(PathBuf::from(path));
+            }
+        }
+        Ok(index)
+    }
+
+    pub fn assets(&self) -> impl Iterator<Item = &AssetUuid> {
+        self.assets.keys()
+    }
+
+    pub fn paths(&self) -> impl Iterator<Item = &PathBuf> {
+        self.paths.iter()
+    }
+}
+
+impl LayerIndex for PackfileIndex {
+    fn contains_asset(&self, id: &AssetUuid) -> bool {
+        self.assets.contains_key(id)
+    }
+
+    fn contains_path(&self, path: &Path) -> bool {
+        self.paths.contains(path)
+    }
+
+    fn artifact_metadata(&self, id: &AssetUuid) -> Option<ArtifactMetadata> {
+        self.assets.get(id).cloned()
+    }
+}
+
+/// Opens the packfile and reads its index.
+///
+/// Blocks on reading the entries of the packfile, but not on the artifacts themselves.
+pub(crate) fn packfile_layer(
+    settings: &PackfileSettings,
+) -> Result<(Layer, Arc<PackfileIndex>), PackfileError> {
+    let (reader, index) = match *settings {
+        #[cfg(not(target_family = "wasm"))]
+        PackfileSettings::Path(ref path) => {
+            let file = std::fs::File::open(path)?;
+            // Safety: like the `PackfileReader`, this assumes the file isn't modified while it is mounted
+            let map = unsafe { memmap2::Mmap::map(&file)? };
+            // only touches the pages of the entries, the artifacts are read when they are loaded
+            let index = PackfileIndex::read(&map)?;
+            drop(map);
+            let reader =
+                PackfileReader::new_from_file(file).map_err(|e| PackfileError::Reader(e.into()))?;
+            (reader, index)
+        }
+        PackfileSettings::Static(bytes) => {
+            let index = PackfileIndex::read(bytes)?;
+            let reader = PackfileReader::new_from_buffer(bytes)
+                .map_err(|e| PackfileError::Reader(e.into()))?;
+            (reader, index)
+        }
+    };
+
+    let index = Arc::new(index);
+    let layer = Layer {
+        io: Box::new(reader) as Box<dyn LoaderIO>,
+        index: Some(Arc::clone(&index) as Arc<dyn LayerIndex>),
+    };
+    Ok((layer, index))
+}
@@ -0,0 +1,440 @@
+use std::collections::HashSet;
+use std::io::Read;
+use std::net::{Shutdown, SocketAddr, TcpStream};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use distill_core::{AssetRef, AssetUuid};
+use distill_loader::crossbeam_channel::Sender;
+use distill_loader::io::{DataRequest, LoaderIO, MetadataRequest, ResolveRequest};
+use distill_loader::loader::LoaderState;
+use distill_loader::rpc_io::RpcConnectionType;
+use distill_loader::storage::LoaderInfoProvider;
+use distill_loader::RpcIO;
+
+use super::{identifier_path, AssetConnectionStatus};
+
+const INITIAL_BACKOFF: Duration = Duration::from_millis(250);
+const MAX_BACKOFF: Duration = Duration::from_secs(10);
+const CONNECT_TIMEOUT: Duration = Duration::from_millis(200);
+const PROBE_INTERVAL: Duration = Duration::from_secs(1);
+const PRUNE_INTERVAL: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum RpcConnection {
+    Tcp,
+    Websocket,
+}
+
+/// Wraps an [`RpcIO`] so that a daemon which is not running yet or restarts doesn't take
+/// the app down with it.
+///
+/// For TCP, a second connection to the daemon is held open next to the [`RpcIO`], which the daemon
+/// only closes when it stops, so a daemon that went away or restarted is noticed without
+/// connecting to it repeatedly. A websocket server closes connections that never complete the handshake,
+/// so for websockets the daemon is probed with a new connection every second instead.
+/// While the daemon is gone, requests are queued and the [`RpcIO`] is dropped.
+/// Once a new connection is established, the queued requests are forwarded to it and everything
+/// requested over the lost connection is requested again, which also picks up assets the daemon
+/// imported in the meantime.
+pub(crate) struct ReconnectingIO {
+    address: SocketAddr,
+    connection: RpcConnection,
+    inner: Option<RpcIO>,
+    watch: Option<Watch>,
+    status: AssetConnectionStatus,
+    status_sender: Sender<AssetConnectionStatus>,
+    attempts: u32,
+    next_attempt: Instant,
+
+    pending_metadata: Vec<MetadataRequest>,
+    pending_resolve: Vec<ResolveRequest>,
+    pending_data: Vec<DataRequest>,
+    /// Assets requested over the current connection which the loader still holds.
+    requested_assets: HashSet<AssetUuid>,
+    /// Paths requested over the current connection since the last prune.
+    requested_paths: HashSet<PathBuf>,
+    /// Paths requested in the interval before the last prune, which may still be in flight.
+    previous_paths: HashSet<PathBuf>,
+    next_prune: Instant,
+}
+
+/// Notices when the daemon goes away.
+struct Watch {
+    /// The connection held open to a TCP daemon, `None` for websockets.
+    stream: Option<TcpStream>,
+    open: Arc<AtomicBool>,
+}
+
+impl Watch {
+    fn connect(address: SocketAddr, connection: RpcConnection) -> std::io::Result<Self> {
+        let stream = TcpStream::connect_timeout(&address, CONNECT_TIMEOUT)?;
+        let open = Arc::new(AtomicBool::new(true));
+        let thread_open = Arc::clone(&open);
+        let thread = std::thread::Builder::new().name("asset daemon watch".to_string());
+        match connection {
+            RpcConnection::Tcp => {
+                let mut reader = stream.try_clone()?;
+                thread.spawn(move || {
+                    // the daemon never answers on this connection, a read returns once it is closed
+                    let mut buf = [0; 64];
+                    while matches!(reader.read(&mut buf), Ok(n) if n > 0) {}
+                    thread_open.store(false, Ordering::Relaxed);
+                })?;
+                Ok(Watch {
+                    stream: Some(stream),
+                    open,
+                })
+            }
+            RpcConnection::Websocket => {
+                drop(stream);
+                thread.spawn(move || loop {
+                    std::thread::sleep(PROBE_INTERVAL);
+                    // the `Watch` was dropped
+                    if Arc::strong_count(&thread_open) == 1 {
+                        break;
+                    }
+                    if TcpStream::connect_timeout(&address, CONNECT_TIMEOUT).is_err() {
+                        thread_open.store(false, Ordering::Relaxed);
+                        break;
+                    }
+                })?;
+                Ok(Watch { stream: None, open })
+            }
+        }
+    }
+
+    fn is_open(&self) -> bool {
+        self.open.load(Ordering::Relaxed)
+    }
+}
+
+impl Drop for Watch {
+    fn drop(&mut self) {
+        // wakes up the watch thread
+        if let Some(stream) = &self.stream {
+            let _ = stream.shutdown(Shutdown::Both);
+        }
+    }
+}
+
+impl ReconnectingIO {
+    pub fn new(
+        address: SocketAddr,
+        connection: RpcConnection,
+        status_sender: Sender<AssetConnectionStatus>,
+    ) -> Self {
+        let _ = status_sender.send(AssetConnectionStatus::Connecting);
+
+        ReconnectingIO {
+            address,
+            connection,
+            inner: None,
+            watch: None,
+            status: AssetConnectionStatus::Connecting,
+            status_sender,
+            attempts: 0,
+            next_attempt: Instant::now(),
+            pending_metadata: Vec::new(),
+            pending_resolve: Vec::new(),
+            pending_data: Vec::new(),
+            requested_assets: HashSet::new(),
+            requested_paths: HashSet::new(),
+            previous_paths: HashSet::new(),
+            next_prune: Instant::now() + PRUNE_INTERVAL,
+        }
+    }
+
+    fn set_status(&mut self, status: AssetConnectionStatus) {
+        if self.status != status {
+            self.status = status;
+            let _ = self.status_sender.send(status);
+        }
+    }
+
+    fn connect(&mut self, loader: &mut LoaderState) {
+        let watch = match Watch::connect(self.address, self.connection) {
+            Ok(watch) => watch,
+            Err(e) => {
+                self.schedule_retry();
+                bevy_log::debug!(
+                    "asset daemon at {} is not reachable: {}, retrying in {:?}",
+                    self.address,
+                    e,
+                    self.next_attempt - Instant::now()
+                );
+                return;
+            }
+        };
+
+        let address = self.address.to_string();
+        let connection = match self.connection {
+            RpcConnection::Tcp => RpcConnectionType::TCP(address),
+            RpcConnection::Websocket => RpcConnectionType::Websocket(address),
+        };
+
+        match RpcIO::new(connection) {
+            Ok(io) => {
+                bevy_log::info!("connected to asset daemon at {}", self.address);
+                self.inner = Some(io);
+                self.watch = Some(watch);
+                self.attempts = 0;
+                self.set_status(AssetConnectionStatus::Connected);
+
+                // requests that were in flight when the connection was lost are never answered
+                self.prune_requested(|id| is_loading(loader, id));
+                let assets: Vec<_> = self.requested_assets.drain().collect();
+                let mut paths: Vec<_> = self.requested_paths.drain().collect();
+                paths.extend(self.previous_paths.drain());
+                if !assets.is_empty() {
+                    loader.invalidate_assets(&assets);
+                }
+                if !paths.is_empty() {
+                    loader.invalidate_paths(&paths);
+                }
+
+                for request in std::mem::take(&mut self.pending_metadata) {
+                    self.get_asset_metadata_with_dependencies(request);
+                }
+                if !self.pending_resolve.is_empty() {
+                    self.get_asset_candidates(std::mem::take(&mut self.pending_resolve));
+                }
+                if !self.pending_data.is_empty() {
+                    self.get_artifacts(std::mem::take(&mut self.pending_data));
+                }
+            }
+            Err(e) => {
+                self.schedule_retry();
+                bevy_log::warn!(
+                    "failed to connect to asset daemon at {}: {}, retrying in {:?}",
+                    self.address,
+                    e,
+                    self.next_attempt - Instant::now()
+                );
+            }
+        }
+    }
+
+    /// Forgets the requests that can't be in flight anymore.
+    ///
+    /// Assets the loader released don't need to be requested again. Paths are resolved
+    /// by the daemon right away, so only the ones requested within the last prune interval are kept.
+    fn prune_requested(&mut self, is_loading: impl Fn(&AssetUuid) -> bool) {
+        self.requested_assets.retain(|id| is_loading(id));
+        self.previous_paths = std::mem::take(&mut self.requested_paths);
+        self.next_prune = Instant::now() + PRUNE_INTERVAL;
+    }
+
+    fn schedule_retry(&mut self) {
+        let backoff = INITIAL_BACKOFF
+            .checked_mul(1 << self.attempts.min(16))
+            .map_or(MAX_BACKOFF, |backoff| backoff.min(MAX_BACKOFF));
+        self.attempts += 1;
+        self.next_attempt = Instant::now() + backoff;
+
+        if self.status != AssetConnectionStatus::Connecting {
+            self.set_status(AssetConnectionStatus::Disconnected {
+                attempts: self.attempts,
+            });
+        }
+    }
+}
+
+impl LoaderIO for ReconnectingIO {
+    fn get_asset_metadata_with_dependencies(&mut self, request: MetadataRequest) {
+        match &mut self.inner {
+            Some(io) => {
+                self.requested_assets
+                    .extend(request.requested_assets().copied());
+                io.get_asset_metadata_with_dependencies(request);
+            }
+            None => self.pending_metadata.push(request),
+        }
+    }
+
+    fn get_asset_candidates(&mut self, requests: Vec<ResolveRequest>) {
+        match &mut self.inner {
+            Some(io) => {
+                self.requested_paths.extend(
+                    requests
+                        .iter()
+                        .map(|request| PathBuf::from(identifier_path(request.identifier()))),
+                );
+                io.get_asset_candidates(requests);
+            }
+            None => self.pending_resolve.extend(requests),
+        }
+    }
+
+    fn get_artifacts(&mut self, requests: Vec<DataRequest>) {
+        match &mut self.inner {
+            Some(io) => {
+                self.requested_assets
+                    .extend(requests.iter().map(DataRequest::asset_id));
+                io.get_artifacts(requests);
+            }
+            None => self.pending_data.extend(requests),
+        }
+    }
+
+    fn tick(&mut self, loader: &mut LoaderState) {
+        if self.watch.as_ref().map_or(false, |watch| !watch.is_open()) {
+            bevy_log::warn!("lost connection to asset daemon at {}", self.address);
+            // dropping the `RpcIO` shuts down its runtime, the requests sent over it are
+            // requested again after reconnecting
+            self.inner = None;
+            self.watch = None;
+            self.attempts = 0;
+            self.next_attempt = Instant::now();
+            self.set_status(AssetConnectionStatus::Disconnected { attempts: 0 });
+        }
+
+        if self.inner.is_some() && Instant::now() >= self.next_prune {
+            self.prune_requested(|id| is_loading(loader, id));
+        }
+
+        match &mut self.inner {
+            Some(io) => io.tick(loader),
+            None if Instant::now() >= self.next_attempt => self.connect(loader),
+            None => {}
+        }
+    }
+}
+
+fn is_loading(loader: &LoaderState, id: &AssetUuid) -> bool {
+    loader.get_load_handle(&AssetRef::Uuid(*id)).is_some()
+}
+
+#[cfg(all(test, feature = "asset-daemon"))]
+mod tests {
+    use std::net::TcpListener;
+    use std::time::{Duration, Instant};
+
+    use bevy_app::App;
+    use bevy_ecs::prelude::*;
+    use distill_loader::crossbeam_channel::unbounded;
+
+    use super::*;
+    use crate::daemon::AssetDaemonHandle;
+    use crate::prelude::*;
+
+    #[derive(Default)]
+    struct ReceivedEvents(Vec<AssetConnectionEvent>);
+
+    fn record_events(
+        mut reader: EventReader<AssetConnectionEvent>,
+        mut received: ResMut<ReceivedEvents>,
+    ) {
+        received.0.extend(reader.iter().copied());
+    }
+
+    /// Updates `app` until the connection status matches, panics if it doesn't within 20 seconds.
+    fn update_until(app: &mut App, matches: impl Fn(AssetConnectionStatus) -> bool) {
+        let deadline = Instant::now() + Duration::from_secs(20);
+        loop {
+            app.update();
+            if matches(*app.world.get_resource::<AssetConnectionStatus>().unwrap()) {
+                return;
+            }
+            assert!(Instant::now() < deadline, "connection status didn't change");
+            std::thread::sleep(Duration::from_millis(20));
+        }
+    }
+
+    #[test]
+    fn reports_lost_and_restored_daemon() {
+        let dir = std::env::temp_dir().join(format!("bevy_asset_reconnect_{}", std::process::id()));
+        let asset_dir = dir.join("assets");
+        std::fs::create_dir_all(&asset_dir).unwrap();
+        let port = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let settings = AssetDaemonSettings::builder()
+            .asset_dir(&asset_dir)
+            .db_path(dir.join("db"))
+            .port(port)
+            .build()
+            .unwrap();
+
+        let mut app = App::new();
+        app.insert_resource(AssetServerSettings::Daemon(settings.clone()))
+            .add_plugin(AssetPlugin)
+            .init_resource::<ReceivedEvents>()
+            .add_system(record_events);
+        update_until(&mut app, |status| {
+            status == AssetConnectionStatus::Connected
+        });
+
+        app.world
+            .get_resource_mut::<AssetDaemonHandle>()
+            .unwrap()
+            .stop()
+            .unwrap();
+        update_until(&mut app, |status| {
+            matches!(status, AssetConnectionStatus::Disconnected { .. })
+        });
+
+        app.insert_resource(AssetDaemonHandle::start(&settings, Vec::new()));
+        update_until(&mut app, |status| {
+            status == AssetConnectionStatus::Connected
+        });
+        // the events of the last update are only read in the next one
+        app.update();
+
+        assert_eq!(
+            app.world.get_resource::<ReceivedEvents>().unwrap().0,
+            [
+                AssetConnectionEvent::Connected,
+                AssetConnectionEvent::Disconnected,
+                AssetConnectionEvent::Reconnected,
+            ]
+        );
+
+        drop(app);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn websocket_watch_notices_stopped_server() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let watch = Watch::connect(address, RpcConnection::Websocket).unwrap();
+        std::thread::sleep(PROBE_INTERVAL * 2);
+        assert!(watch.is_open());
+
+        drop(listener);
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while watch.is_open() {
+            assert!(
+                Instant::now() < deadline,
+                "the stopped server wasn't noticed"
+            );
+            std::thread::sleep(Duration::from_millis(20));
+        }
+    }
+
+    #[test]
+    fn prunes_released_assets_and_answered_paths() {
+        let (status_sender, _status_receiver) = unbounded();
+        let address = ([127, 0, 0, 1], 0).into();
+        let mut io = ReconnectingIO::new(address, RpcConnection::Tcp, status_sender);
+        let loaded = AssetUuid([1; 16]);
+        let released = AssetUuid([2; 16]);
+        io.requested_assets.extend([loaded, released]);
+        io.requested_paths.insert(PathBuf::from("a.txt"));
+
+        io.prune_requested(|id| *id == loaded);
+        assert_eq!(io.requested_assets, HashSet::from([loaded]));
+        // kept for one more interval in case it is still in flight
+        assert_eq!(io.previous_paths, HashSet::from([PathBuf::from("a.txt")]));
+
+        io.requested_paths.insert(PathBuf::from("b.txt"));
+        io.prune_requested(|id| *id == loaded);
+        assert_eq!(io.previous_paths, HashSet::from([PathBuf::from("b.txt")]));
+        assert!(io.requested_paths.is_empty());
+    }
+}
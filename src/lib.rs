@@ -1,28 +1,64 @@
 mod asset_server;
+#[cfg(feature = "asset-daemon")]
+mod daemon;
 mod handle;
 pub mod importer;
+mod io;
+#[cfg(all(feature = "packfile", not(target_family = "wasm")))]
+pub mod packfile;
 mod plugin;
+pub mod processor;
+pub mod schema;
+mod settings;
 mod storage;
 pub mod util;
+#[cfg(not(target_family = "wasm"))]
+pub mod uuids;
 
 pub use asset_server::AssetServer;
 use bevy_reflect::TypeUuid;
+#[cfg(feature = "asset-daemon")]
+pub use daemon::{AssetDaemonError, AssetDaemonEvent, AssetDaemonHandle};
 
 use distill_core::TypeUuidDynamic;
 
 pub use distill_importer;
 pub use handle::{Handle, HandleUntyped, WeakHandle};
+pub use io::{AssetConnectionEvent, AssetConnectionStatus, MemoryAssetSource};
+#[cfg(feature = "packfile")]
+pub use io::{PackfileError, PackfileId};
 pub use plugin::{AddAsset, AssetPlugin, AssetStage};
+#[cfg(all(feature = "rpc-io", not(target_family = "wasm")))]
+pub use settings::AssetDaemonTcpSettings;
+#[cfg(feature = "rpc-io")]
+pub use settings::AssetDaemonWebsocketSettings;
+#[cfg(not(target_family = "wasm"))]
+pub use settings::DirectorySettings;
+#[cfg(feature = "packfile")]
+pub use settings::PackfileSettings;
+#[cfg(feature = "asset-daemon")]
+pub use settings::{AssetDaemonSettings, AssetDaemonSettingsBuilder};
+pub use settings::{AssetLoadBudget, AssetServerSettings, AssetSettingsError};
 pub use storage::Assets;
 
 pub mod prelude {
     pub use crate::handle::{Handle, HandleUntyped, WeakHandle};
+    pub use crate::plugin::{AddAsset, AssetPlugin};
     #[cfg(feature = "asset-daemon")]
-    pub use crate::plugin::AssetDaemonSettings;
+    pub use crate::settings::AssetDaemonSettings;
+    #[cfg(all(feature = "rpc-io", not(target_family = "wasm")))]
+    pub use crate::settings::AssetDaemonTcpSettings;
+    #[cfg(feature = "rpc-io")]
+    pub use crate::settings::AssetDaemonWebsocketSettings;
+    #[cfg(not(target_family = "wasm"))]
+    pub use crate::settings::DirectorySettings;
     #[cfg(feature = "packfile")]
-    pub use crate::plugin::PackfileSettings;
-    pub use crate::plugin::{AddAsset, AssetPlugin, AssetServerSettings};
-    pub use crate::{Asset, AssetEvent, AssetServer, Assets};
+    pub use crate::settings::PackfileSettings;
+    pub use crate::settings::{AssetLoadBudget, AssetServerSettings};
+    pub use crate::{
+        Asset, AssetConnectionEvent, AssetConnectionStatus, AssetEvent, AssetServer, Assets,
+        MemoryAssetSource,
+    };
 
     pub use bevy_reflect::TypeUuid;
 
@@ -0,0 +1,177 @@
+//! Building packfiles which can be loaded with [`PackfileSettings`](crate::PackfileSettings).
+//!
+//! The `bevy-asset-pack` binary only knows the importers of this crate.
+//! For custom assets, call [`build_from_app`] from your own binary after registering the importers.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use bevy_app::App;
+use bevy_utils::HashMap;
+use distill_core::AssetUuid;
+use distill_importer::BoxedImporter;
+
+pub use crate::io::import::ImportError;
+use crate::io::import::{self, ImportedArtifact};
+use crate::plugin::{registered_importers, registered_processors, registered_schemas};
+
+#[derive(Debug)]
+pub enum PackfileBuildError {
+    Io(PathBuf, std::io::Error),
+    Import(ImportError),
+    /// Two source files contain an asset with the same UUID.
+    DuplicateAsset(AssetUuid, PathBuf, PathBuf),
+    Capnp(capnp::Error),
+}
+
+impl fmt::Display for PackfileBuildError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PackfileBuildError::Io(path, e) => {
+                write!(f, "failed to access {}: {}", path.display(), e)
+            }
+            PackfileBuildError::Import(e) => write!(f, "{}", e),
+            PackfileBuildError::DuplicateAsset(id, a, b) => write!(
+                f,
+                "asset {:?} is contained in both {} and {}",
+                id,
+                a.display(),
+                b.display()
+            ),
+            PackfileBuildError::Capnp(e) => write!(f, "failed to write packfile: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for PackfileBuildError {}
+
+impl From<ImportError> for PackfileBuildError {
+    fn from(e: ImportError) -> Self {
+        PackfileBuildError::Import(e)
+    }
+}
+impl From<capnp::Error> for PackfileBuildError {
+    fn from(e: capnp::Error) -> Self {
+        PackfileBuildError::Capnp(e)
+    }
+}
+
+/// Builds a packfile with the importers registered on `app` through [`AddAsset`](crate::AddAsset).
+///
+/// The assets are processed by the registered [`AssetProcessor`](crate::processor::AssetProcessor)s.
+pub fn build_from_app(
+    app: &App,
+    asset_dirs: &[impl AsRef<Path>],
+    out_path: impl AsRef<Path>,
+    deterministic_uuids: bool,
+) -> Result<usize, PackfileBuildError> {
+    let importers = registered_importers(&app.world);
+    let importers = import::boxed_importers(
+        &importers.read().unwrap(),
+        &registered_schemas(&app.world),
+        Some(&registered_processors(&app.world)),
+    );
+    build(asset_dirs, &importers, out_path, deterministic_uuids)
+}
+
+/// Imports every file in `asset_dirs` and writes the assets to a packfile at `out_path`.
+///
+/// Paths in the packfile are relative to the asset dir containing the file.
+/// Entries are sorted by path and UUID, so the same sources always produce the same packfile,
+/// as long as every source has a `.meta` file with its UUIDs or `deterministic_uuids` is set.
+/// See [`deterministic_asset_uuid`](crate::util::deterministic_asset_uuid).
+///
+/// Returns the number of assets written.
+pub fn build(
+    asset_dirs: &[impl AsRef<Path>],
+    importers: &[(&'static [&'static str], Box<dyn BoxedImporter>)],
+    out_path: impl AsRef<Path>,
+    deterministic_uuids: bool,
+) -> Result<usize, PackfileBuildError> {
+    let mut imported = Vec::new();
+    let mut owners: HashMap<AssetUuid, PathBuf> = HashMap::default();
+    let mut sources: BTreeMap<PathBuf, Vec<AssetUuid>> = BTreeMap::new();
+
+    for dir in asset_dirs {
+        let dir = dir.as_ref();
+        let mut files = Vec::new();
+        import::collect_sources(dir, &mut files)
+            .map_err(|e| PackfileBuildError::Io(dir.to_path_buf(), e))?;
+        files.sort();
+
+        for path in files {
+            let importer = match import::find_importer(importers, &path) {
+                Some(i) => &importers[i].1,
+                None => continue,
+            };
+            let relative = path.strip_prefix(dir).unwrap_or(&path).to_path_buf();
+
+            let source =
+                import::import_source(&**importer, &path, deterministic_uuids.then(|| dir), None)?;
+            let ids: Vec<_> = source.asset_ids().collect();
+            bevy_log::debug!("imported {} ({} assets)", relative.display(), ids.len());
+
+            for id in &ids {
+                if let Some(other) = owners.get(id) {
+                    return Err(PackfileBuildError::DuplicateAsset(
+                        *id,
+                        other.clone(),
+                        relative,
+                    ));
+                }
+                owners.insert(*id, relative.clone());
+            }
+            sources.entry(relative.clone()).or_default().extend(ids);
+            imported.push((relative, source));
+        }
+    }
+
+    let resolve_path = |path: &Path| sources.get(path).and_then(|ids| ids.first().copied());
+
+    // handles can only be serialized once every source has been imported
+    let mut assets: HashMap<AssetUuid, (PathBuf, ImportedArtifact)> = HashMap::default();
+    for (relative, mut source) in imported {
+        for artifact in source.serialize(&relative, &resolve_path)? {
+            assets.insert(artifact.id(), (relative.clone(), artifact));
+        }
+    }
+
+    let mut message = capnp::message::Builder::new_default();
+    let packfile = message.init_root::<distill_schema::pack::pack_file::Builder<'_>>();
+    let mut entries = packfile.init_entries(assets.len() as u32);
+
+    let sorted = sources
+        .iter()
+        .flat_map(|(path, ids)| ids.iter().map(move |id| (path, id)));
+    for (i, (path, id)) in sorted.enumerate() {
+        let (_, asset) = &assets[id];
+        let mut metadata = asset.metadata.clone();
+        metadata.artifact = Some(import::resolve_load_deps(
+            asset.artifact(),
+            path,
+            &resolve_path,
+        ));
+
+        let mut entry = entries.reborrow().get(i as u32);
+        distill_schema::build_asset_metadata(
+            &metadata,
+            &mut entry.reborrow().init_asset_metadata(),
+            distill_schema::data::AssetSource::File,
+        );
+        let mut artifact = entry.reborrow().init_artifact();
+        distill_schema::build_artifact_metadata(
+            metadata.artifact.as_ref().unwrap(),
+            &mut artifact.reborrow().init_metadata(),
+        );
+        artifact.set_data(&asset.data);
+        entry.set_path(path.to_string_lossy().replace('\\', "/").as_bytes());
+    }
+
+    let out_path = out_path.as_ref();
+    let mut file = std::fs::File::create(out_path)
+        .map_err(|e| PackfileBuildError::Io(out_path.to_path_buf(), e))?;
+    capnp::serialize::write_message(&mut file, &message)?;
+
+    Ok(assets.len())
+}
@@ -1,19 +1,36 @@
 use std::marker::PhantomData;
+use std::path::PathBuf;
 use std::sync::Arc;
+use std::time::Instant;
 
+#[cfg(feature = "asset-daemon")]
+use crate::daemon::{
+    monitor_asset_daemon, stop_asset_daemon_on_exit, AssetDaemonEvent, AssetDaemonHandle,
+};
+use crate::io::import::{self, SharedImporters};
+use crate::io::{AssetConnectionEvent, AssetConnectionStatus};
 use crate::prelude::*;
-use crate::storage::{AssetResources, WorldAssetStorage};
+use crate::processor::{AssetProcessor, SharedProcessors};
+use crate::schema::{self, AssetSchema, SharedSchemas};
+use crate::settings::LoaderIOContext;
+use crate::storage::{AssetResources, AssetUpdateQueue, WorldAssetStorage};
 use crate::AssetEvent;
 use bevy_app::prelude::*;
 use bevy_ecs::prelude::*;
+#[cfg(feature = "asset-daemon")]
+use bevy_ecs::schedule::ShouldRun;
+#[cfg(feature = "packfile")]
+use bevy_tasks::IoTaskPool;
+use bevy_tasks::{AsyncComputeTaskPool, TaskPoolBuilder};
 
+#[cfg(feature = "packfile")]
+use distill_core::AssetUuid;
 use distill_importer::BoxedImporter;
 use distill_loader::crossbeam_channel::{unbounded, Receiver, Sender};
 use distill_loader::handle::RefOp;
-use distill_loader::io::LoaderIO;
 use distill_loader::storage::{AtomicHandleAllocator, DefaultIndirectionResolver, HandleAllocator};
 use distill_loader::{self, Loader};
-use serde::de::DeserializeSeed;
+use serde::de::{DeserializeOwned, DeserializeSeed};
 
 #[derive(StageLabel, Debug, Clone, Hash, PartialEq, Eq)]
 pub enum AssetStage {
@@ -23,142 +40,7 @@ pub enum AssetStage {
 #[derive(SystemLabel, Debug, Clone, Hash, PartialEq, Eq)]
 enum AssetSystem {
     ProcessAssetEvents,
-}
-
-#[derive(Debug, Clone)]
-pub enum AssetServerSettings {
-    #[cfg(feature = "asset-daemon")]
-    Daemon(AssetDaemonSettings),
-    #[cfg(feature = "rpc-io")]
-    DaemonWebsocket(AssetDaemonWebsocketSettings),
-    #[cfg(feature = "packfile")]
-    Packfile(PackfileSettings),
-}
-impl AssetServerSettings {
-    fn default_fallback() -> Option<Self> {
-        #[cfg(feature = "asset-daemon")]
-        return Some(AssetServerSettings::Daemon(AssetDaemonSettings::default()));
-        #[cfg(all(not(feature = "asset-daemon"), feature = "rpc-io"))]
-        #[cfg(all(
-            not(feature = "asset-daemon"),
-            feature = "rpc-io",
-            target_arch = "wasm32"
-        ))]
-        return Some(AssetServerSettings::DaemonWebsocket(
-            AssetDaemonWebsocketSettings::default(),
-        ));
-        #[cfg(not(any(
-            feature = "asset-daemon",
-            all(
-                not(feature = "asset-daemon"),
-                feature = "rpc-io",
-                target_arch = "wasm32"
-            )
-        )))]
-        return None;
-    }
-}
-
-#[cfg(feature = "packfile")]
-#[derive(Debug, Clone)]
-pub enum PackfileSettings {
-    #[cfg(not(target_family = "wasm"))]
-    Path(std::path::PathBuf),
-    Static(&'static [u8]),
-}
-
-#[cfg(feature = "asset-daemon")]
-#[derive(Debug, Clone)]
-pub struct AssetDaemonSettings {
-    asset_dirs: Vec<std::path::PathBuf>,
-    db_path: std::path::PathBuf,
-    address: std::net::SocketAddr,
-    clear_db_on_start: bool,
-}
-
-#[cfg(feature = "asset-daemon")]
-impl Default for AssetDaemonSettings {
-    fn default() -> Self {
-        AssetDaemonSettings {
-            asset_dirs: vec![std::path::PathBuf::from("assets")],
-            db_path: std::path::PathBuf::from(".assets_db"),
-            address: ([127, 0, 0, 1], 9999).into(),
-            clear_db_on_start: false,
-        }
-    }
-}
-
-#[cfg(feature = "rpc-io")]
-#[derive(Debug, Clone)]
-pub struct AssetDaemonWebsocketSettings {
-    address: std::net::SocketAddr,
-}
-#[cfg(feature = "rpc-io")]
-impl Default for AssetDaemonWebsocketSettings {
-    fn default() -> Self {
-        AssetDaemonWebsocketSettings {
-            address: ([127, 0, 0, 1], 9998).into(),
-        }
-    }
-}
-
-impl AssetServerSettings {
-    #[cfg(feature = "asset-daemon")]
-    fn daemon(&self, asset_loaders: Vec<AssetLoader>) -> Option<distill_daemon::AssetDaemon> {
-        match *self {
-            AssetServerSettings::Daemon(AssetDaemonSettings {
-                ref asset_dirs,
-                ref db_path,
-                ref address,
-                clear_db_on_start,
-            }) => {
-                let db_path = db_path.clone();
-                let address = address.clone();
-                let mut asset_daemon = distill_daemon::AssetDaemon::default()
-                    .with_db_path(db_path)
-                    .with_address(address)
-                    .with_importers_boxed(asset_loaders)
-                    .with_asset_dirs(asset_dirs.clone());
-                if clear_db_on_start {
-                    asset_daemon = asset_daemon.with_clear_db_on_start();
-                }
-                Some(asset_daemon)
-            }
-            #[allow(unreachable_patterns)]
-            _ => None,
-        }
-    }
-
-    fn loader_io(&self) -> Result<Box<dyn LoaderIO>, Box<dyn std::error::Error>> {
-        match *self {
-            #[cfg(feature = "asset-daemon")]
-            AssetServerSettings::Daemon(ref settings) => Ok(Box::new(
-                distill_loader::RpcIO::new(distill_loader::rpc_io::RpcConnectionType::TCP(
-                    settings.address.to_string(),
-                ))
-                .unwrap(),
-            )),
-            #[cfg(feature = "rpc-io")]
-            AssetServerSettings::DaemonWebsocket(ref settings) => Ok(Box::new(
-                distill_loader::RpcIO::new(distill_loader::rpc_io::RpcConnectionType::Websocket(
-                    settings.address.to_string(),
-                ))
-                .unwrap(),
-            )),
-            #[cfg(feature = "packfile")]
-            #[cfg(not(target_family = "wasm"))]
-            AssetServerSettings::Packfile(PackfileSettings::Path(ref path)) => {
-                let file = std::fs::File::open(path)?;
-                Ok(Box::new(distill_loader::PackfileReader::new_from_file(
-                    file,
-                )?))
-            }
-            #[cfg(feature = "packfile")]
-            AssetServerSettings::Packfile(PackfileSettings::Static(bytes)) => Ok(Box::new(
-                distill_loader::PackfileReader::new_from_buffer(bytes)?,
-            )),
-        }
-    }
+    UpdateConnectionStatus,
 }
 
 #[derive(Default)]
@@ -167,16 +49,24 @@ pub struct AssetPlugin;
 struct RefopReceiver(Receiver<RefOp>);
 struct RefopSender(Arc<Sender<RefOp>>);
 struct AssetHandleAllocator(Arc<dyn HandleAllocator>);
+struct ConnectionStatusReceiver(Receiver<AssetConnectionStatus>);
+/// Assets which no asset source provides anymore after unmounting a packfile.
+#[cfg(feature = "packfile")]
+struct RemovedAssetsReceiver(Receiver<Vec<AssetUuid>>);
 
-type AssetLoader = (&'static [&'static str], Box<dyn BoxedImporter + 'static>);
+pub(crate) type AssetLoader = (&'static [&'static str], Arc<dyn BoxedImporter + 'static>);
 #[derive(Default)]
-struct AssetLoaders(Vec<AssetLoader>);
+struct AssetLoaders(SharedImporters);
+#[derive(Default)]
+struct AssetSchemas(SharedSchemas);
+#[derive(Default)]
+struct AssetProcessors(SharedProcessors);
 
 impl Plugin for AssetPlugin {
     fn build(&self, app: &mut App) {
         let world = &mut app.world;
 
-        let asset_server_settings = world.get_resource_or_insert_with(|| {
+        world.get_resource_or_insert_with(|| {
             AssetServerSettings::default_fallback()
                 .unwrap_or_else(|| panic!("missing `AssetServerSettings` resource. Either insert it or enable the `asset-daemon` feature or enable `rpc-io` and start the daemon yourself"))
         });
@@ -184,44 +74,199 @@ impl Plugin for AssetPlugin {
         let (refop_sender, refop_receiver) = unbounded();
         let refop_sender = Arc::new(refop_sender);
 
-        let loader_io = asset_server_settings
-            .loader_io()
+        let (status_sender, status_receiver) = unbounded();
+        let context = LoaderIOContext {
+            status_sender,
+            importers: Arc::clone(&world.get_resource_or_insert_with(AssetLoaders::default).0),
+            schemas: Arc::clone(&world.get_resource_or_insert_with(AssetSchemas::default).0),
+            processors: Arc::clone(
+                &world
+                    .get_resource_or_insert_with(AssetProcessors::default)
+                    .0,
+            ),
+            // assets are deserialized on the `AsyncComputeTaskPool`, so make sure there is one
+            task_pool: (**world.get_resource_or_insert_with(|| {
+                AsyncComputeTaskPool(
+                    TaskPoolBuilder::new()
+                        .thread_name("Async Compute Task Pool".to_string())
+                        .build(),
+                )
+            }))
+            .clone(),
+        };
+        let settings = world.get_resource::<AssetServerSettings>().unwrap();
+        #[cfg(not(feature = "packfile"))]
+        let loader_io = settings
+            .loader_io(&context)
             .expect("failed to create asset loader IO");
+        #[cfg(feature = "packfile")]
+        let (mount_sender, mount_receiver) = unbounded();
+        #[cfg(feature = "packfile")]
+        let (removed_sender, removed_receiver) = unbounded();
+        #[cfg(feature = "packfile")]
+        let loader_io = Box::new(
+            settings
+                .layered_io(&context)
+                .expect("failed to create asset loader IO")
+                .with_mounts(mount_receiver, removed_sender),
+        );
 
         let handle_allocator =
             Arc::new(AtomicHandleAllocator::default()) as Arc<dyn HandleAllocator>;
         let loader = Loader::new_with_handle_allocator(loader_io, Arc::clone(&handle_allocator));
-        let asset_server = AssetServer::new(loader, Arc::clone(&refop_sender));
+        let asset_server = AssetServer::new(loader, Arc::clone(&refop_sender))
+            .with_importers(Arc::clone(&context.importers));
+        // packfiles are mounted on the `IoTaskPool`
+        #[cfg(feature = "packfile")]
+        let io_task_pool = (**app.world.get_resource_or_insert_with(|| {
+            IoTaskPool(
+                TaskPoolBuilder::new()
+                    .thread_name("IO Task Pool".to_string())
+                    .build(),
+            )
+        }))
+        .clone();
+        #[cfg(feature = "packfile")]
+        let asset_server = asset_server.with_mount_sender(mount_sender, io_task_pool);
 
         app.register_type::<HandleUntyped>()
+            .init_resource::<AssetLoadBudget>()
+            .init_resource::<AssetUpdateQueue>()
             .init_resource::<AssetResources>()
             .insert_resource(asset_server)
             .insert_resource(RefopReceiver(refop_receiver))
             .insert_resource(RefopSender(refop_sender))
             .insert_resource(AssetHandleAllocator(handle_allocator))
+            .insert_resource(ConnectionStatusReceiver(status_receiver))
+            .init_resource::<AssetConnectionStatus>()
+            .add_event::<AssetConnectionEvent>()
             .add_stage_before(
                 CoreStage::PreUpdate,
                 AssetStage::LoadAssets,
-                SystemStage::parallel().with_system(
-                    process_asset_events
-                        .exclusive_system()
-                        .at_start()
-                        .label(AssetSystem::ProcessAssetEvents),
-                ),
+                SystemStage::parallel()
+                    .with_system(
+                        process_asset_events
+                            .exclusive_system()
+                            .at_start()
+                            .label(AssetSystem::ProcessAssetEvents),
+                    )
+                    .with_system(
+                        update_connection_status.label(AssetSystem::UpdateConnectionStatus),
+                    ),
             );
+        #[cfg(feature = "packfile")]
+        app.insert_resource(RemovedAssetsReceiver(removed_receiver));
         #[cfg(feature = "asset-daemon")]
-        app.add_startup_system(start_asset_daemon);
+        app.add_event::<AssetDaemonEvent>()
+            .add_startup_system(start_asset_daemon.exclusive_system())
+            .add_system_to_stage(
+                AssetStage::LoadAssets,
+                monitor_asset_daemon.with_run_criteria(has_asset_daemon),
+            )
+            .add_system_to_stage(
+                AssetStage::LoadAssets,
+                forward_importers_to_daemon.with_run_criteria(has_asset_daemon),
+            )
+            .add_system_to_stage(CoreStage::Last, stop_asset_daemon_on_exit);
     }
 }
 
+/// An app loading its assets from `source`, for tests.
+#[cfg(test)]
+pub(crate) fn in_memory_app(source: crate::io::MemoryAssetSource) -> App {
+    let mut app = App::new();
+    app.insert_resource(AssetServerSettings::InMemory(source))
+        .add_plugin(AssetPlugin);
+    app
+}
+
+/// The importers registered through [`AddAsset`] so far.
+#[cfg(not(target_family = "wasm"))]
+pub(crate) fn registered_importers(world: &World) -> SharedImporters {
+    world
+        .get_resource::<AssetLoaders>()
+        .map(|asset_loaders| Arc::clone(&asset_loaders.0))
+        .unwrap_or_default()
+}
+
+/// The schema versions registered through [`AddAsset::add_asset_schema`] so far.
+#[cfg(not(target_family = "wasm"))]
+pub(crate) fn registered_schemas(world: &World) -> SharedSchemas {
+    world
+        .get_resource::<AssetSchemas>()
+        .map(|asset_schemas| Arc::clone(&asset_schemas.0))
+        .unwrap_or_default()
+}
+
+/// The processors registered through [`AddAsset::add_asset_processor`] so far.
+#[cfg(not(target_family = "wasm"))]
+pub(crate) fn registered_processors(world: &World) -> SharedProcessors {
+    world
+        .get_resource::<AssetProcessors>()
+        .map(|asset_processors| Arc::clone(&asset_processors.0))
+        .unwrap_or_default()
+}
+
 #[cfg(feature = "asset-daemon")]
-fn start_asset_daemon(
-    mut asset_loaders: ResMut<AssetLoaders>,
-    asset_server_settings: Res<AssetServerSettings>,
+fn start_asset_daemon(world: &mut World) {
+    let settings = match world
+        .get_resource::<AssetServerSettings>()
+        .and_then(AssetServerSettings::daemon_settings)
+    {
+        Some(settings) => settings.clone(),
+        None => return,
+    };
+    // importers stay registered, so plugins added after the `AssetPlugin` can still add theirs
+    let schemas = registered_schemas(world);
+    let processors = registered_processors(world);
+    let processors = settings.process_assets().then(|| &processors);
+    let asset_loaders = world
+        .get_resource::<AssetLoaders>()
+        .map(|asset_loaders| {
+            import::boxed_importers(&asset_loaders.0.read().unwrap(), &schemas, processors)
+        })
+        .unwrap_or_default();
+
+    world.insert_resource(AssetDaemonHandle::start(&settings, asset_loaders));
+}
+
+/// The daemon can't add importers while running, so it is restarted when new ones were registered
+/// through [`AssetServer::register_importer`], or when schemas or processors were added after it started.
+/// It imports the files it previously skipped on startup, and everything again if a schema or processor changed.
+/// The restart happens in the background, changes made meanwhile cause another one afterwards.
+#[cfg(feature = "asset-daemon")]
+fn forward_importers_to_daemon(
+    mut daemon: ResMut<AssetDaemonHandle>,
+    settings: Res<AssetServerSettings>,
+    asset_loaders: Res<AssetLoaders>,
+    asset_schemas: Res<AssetSchemas>,
+    asset_processors: Res<AssetProcessors>,
 ) {
-    let asset_loaders = std::mem::take(&mut asset_loaders.0);
-    if let Some(daemon) = asset_server_settings.daemon(asset_loaders) {
-        std::thread::spawn(|| daemon.run());
+    if !daemon.is_running() {
+        return;
+    }
+    let settings = match settings.daemon_settings() {
+        Some(settings) => settings,
+        None => return,
+    };
+
+    let importers = import::boxed_importers(
+        &asset_loaders.0.read().unwrap(),
+        &asset_schemas.0,
+        settings.process_assets().then(|| &asset_processors.0),
+    );
+    if import::importers_version(&importers) == daemon.importers_version() {
+        return;
+    }
+    bevy_log::info!("restarting asset daemon with changed importers, schemas or processors");
+    daemon.restart(settings, importers);
+}
+
+#[cfg(feature = "asset-daemon")]
+fn has_asset_daemon(daemon: Option<Res<AssetDaemonHandle>>) -> ShouldRun {
+    match daemon {
+        Some(_) => ShouldRun::Yes,
+        None => ShouldRun::No,
     }
 }
 
@@ -230,31 +275,116 @@ fn process_asset_events(world: &mut World) {
         let refop_receiver = world.get_resource::<RefopReceiver>().unwrap();
         distill_loader::handle::process_ref_ops(asset_server.loader(), &refop_receiver.0);
 
+        let budget = *world.get_resource::<AssetLoadBudget>().unwrap();
+        // committing the assets handed over last frame happens in `process`, so it counts towards the budget
+        let started = Instant::now();
+
         world.resource_scope(|world, asset_resources: Mut<AssetResources>| {
-            let mut asset_storage = WorldAssetStorage {
-                world,
-                asset_resources: &*asset_resources,
-            };
-
-            asset_server
-                .loader_mut()
-                .process(&mut asset_storage, &DefaultIndirectionResolver)
-                .unwrap();
+            world.resource_scope(|world, mut queue: Mut<AssetUpdateQueue>| {
+                let mut asset_storage = WorldAssetStorage {
+                    world,
+                    asset_resources: &*asset_resources,
+                    queue: &mut *queue,
+                };
+
+                asset_server
+                    .loader_mut()
+                    .process(&mut asset_storage, &DefaultIndirectionResolver)
+                    .unwrap();
+
+                let priorities = asset_server.resolved_load_priorities();
+                asset_storage.update_queued(
+                    asset_server.loader(),
+                    budget,
+                    started,
+                    |load_handle| priorities.get(&load_handle).copied().unwrap_or(0),
+                );
+            });
+
+            #[cfg(feature = "packfile")]
+            remove_unsourced_assets(world, &asset_server, &asset_resources);
         });
     });
 }
 
+#[cfg(feature = "packfile")]
+fn remove_unsourced_assets(
+    world: &mut World,
+    asset_server: &AssetServer,
+    asset_resources: &AssetResources,
+) {
+    let receiver = &world.get_resource::<RemovedAssetsReceiver>().unwrap().0;
+    let load_handles: Vec<_> = receiver
+        .try_iter()
+        .flatten()
+        .filter_map(|id| asset_server.loader().get_load(id))
+        .collect();
+    for load_handle in load_handles {
+        asset_resources.remove(world, load_handle);
+    }
+}
+
+fn update_connection_status(
+    receiver: Res<ConnectionStatusReceiver>,
+    mut status: ResMut<AssetConnectionStatus>,
+    mut events: EventWriter<AssetConnectionEvent>,
+) {
+    for new_status in receiver.0.try_iter() {
+        let event = match (*status, new_status) {
+            (AssetConnectionStatus::Connecting, AssetConnectionStatus::Connected) => {
+                Some(AssetConnectionEvent::Connected)
+            }
+            (AssetConnectionStatus::Disconnected { .. }, AssetConnectionStatus::Connected) => {
+                Some(AssetConnectionEvent::Reconnected)
+            }
+            (AssetConnectionStatus::Connected, AssetConnectionStatus::Disconnected { .. }) => {
+                Some(AssetConnectionEvent::Disconnected)
+            }
+            _ => None,
+        };
+        if let Some(event) = event {
+            events.send(event);
+        }
+        *status = new_status;
+    }
+}
+
 pub trait AddAsset {
     fn add_asset<T: Asset + for<'de> Deserialize<'de>>(&mut self) -> &mut Self;
-    fn add_asset_seeded<T: Asset, D: FromWorld + Clone + for<'de> DeserializeSeed<'de, Value = T>>(
+    fn add_asset_seeded<
+        T: Asset,
+        D: FromWorld + Clone + Send + for<'de> DeserializeSeed<'de, Value = T> + 'static,
+    >(
         &mut self,
     ) -> &mut Self;
     fn add_asset_non_deserialize<T: Asset>(&mut self) -> &mut Self;
+    /// Sets the schema version of an asset type added before, see [`schema`](crate::schema).
+    ///
+    /// Importers don't declare which asset types they produce, so every registered schema version is part
+    /// of the version of every importer: changing one makes the asset daemon import all sources again,
+    /// not just the ones with assets of type `T`.
+    fn add_asset_schema<T: Asset>(&mut self, schema: AssetSchema<T>) -> &mut Self;
+    /// Runs `processor` on every imported asset of type `T`, see [`processor`](crate::processor).
+    ///
+    /// Like schema versions, the version of every registered processor is part of the version of every importer,
+    /// so adding a processor or changing its version makes the asset daemon import all sources again.
+    fn add_asset_processor<T, P>(&mut self, processor: P) -> &mut Self
+    where
+        T: Asset + Serialize + DeserializeOwned,
+        P: AssetProcessor<T>;
+    /// Caches processed assets in `cache_dir` when importing in-process, e.g. for packfile builds.
+    ///
+    /// Entries are never removed, so the directory grows with every changed source or processor version
+    /// until it is cleared by hand.
+    fn set_asset_processor_cache(&mut self, cache_dir: impl Into<PathBuf>) -> &mut Self;
 
     fn init_asset_loader<T: BoxedImporter + FromWorld>(
         &mut self,
         extensions: &'static [&'static str],
     ) -> &mut Self;
+    /// Imports files with one of the `extensions` with `loader`.
+    ///
+    /// If a different importer with the same UUID was added before, `loader` is rejected with an error.
     fn add_asset_loader<T: BoxedImporter>(
         &mut self,
         extensions: &'static [&'static str],
@@ -269,7 +399,7 @@ impl AddAsset for App {
 
     fn add_asset_seeded<
         A: Asset,
-        D: FromWorld + Clone + for<'de> DeserializeSeed<'de, Value = A>,
+        D: FromWorld + Clone + Send + for<'de> DeserializeSeed<'de, Value = A> + 'static,
     >(
         &mut self,
     ) -> &mut Self {
@@ -306,6 +436,39 @@ impl AddAsset for App {
         self
     }
 
+    fn add_asset_schema<A: Asset>(&mut self, schema: AssetSchema<A>) -> &mut Self {
+        let schemas = self
+            .world
+            .get_resource_or_insert_with(AssetSchemas::default);
+        schema::register(&schemas.0, &schema);
+
+        self.world
+            .get_resource_mut::<Assets<A>>()
+            .expect("`add_asset_schema` has to be called after `add_asset`")
+            .set_schema(schema);
+        self
+    }
+
+    fn add_asset_processor<T, P>(&mut self, processor: P) -> &mut Self
+    where
+        T: Asset + Serialize + DeserializeOwned,
+        P: AssetProcessor<T>,
+    {
+        self.world
+            .get_resource_or_insert_with(AssetProcessors::default)
+            .0
+            .register(processor);
+        self
+    }
+
+    fn set_asset_processor_cache(&mut self, cache_dir: impl Into<PathBuf>) -> &mut Self {
+        self.world
+            .get_resource_or_insert_with(AssetProcessors::default)
+            .0
+            .set_cache_dir(Some(cache_dir.into()));
+        self
+    }
+
     fn init_asset_loader<T: BoxedImporter + FromWorld>(
         &mut self,
         extensions: &'static [&'static str],
@@ -319,10 +482,10 @@ impl AddAsset for App {
         extensions: &'static [&'static str],
         loader: T,
     ) -> &mut Self {
-        self.world
-            .get_resource_or_insert_with(AssetLoaders::default)
-            .0
-            .push((extensions, Box::new(loader)));
+        let asset_loaders = self
+            .world
+            .get_resource_or_insert_with(AssetLoaders::default);
+        import::register_importer(&asset_loaders.0, extensions, Arc::new(loader));
         self
     }
 }
@@ -0,0 +1,231 @@
+//! Processing of imported assets before they are stored, like compressing textures or minifying text.
+//!
+//! An [`AssetProcessor`] registered through [`AddAsset::add_asset_processor`](crate::AddAsset::add_asset_processor)
+//! runs on every imported asset of its type, in the asset daemon started by the [`AssetPlugin`](crate::AssetPlugin),
+//! when importing a directory in-process and when building packfiles.
+//! Processed assets have the same type as imported ones, so the code loading them doesn't change.
+//!
+//! Whether the daemon and directories serve raw or processed assets is chosen by
+//! [`AssetDaemonSettingsBuilder::process_assets`](crate::AssetDaemonSettingsBuilder::process_assets) and
+//! [`DirectorySettings::with_processing`](crate::DirectorySettings::with_processing).
+//! Packfiles always contain processed assets.
+//!
+//! The daemon caches processed assets like imported ones, and processes a source again when it,
+//! its importer or a processor version changed. In-process imports can cache processed assets on disk,
+//! see [`AddAsset::set_asset_processor_cache`](crate::AddAsset::set_asset_processor_cache).
+
+use std::error::Error;
+use std::fmt;
+use std::marker::PhantomData;
+use std::path::PathBuf;
+use std::sync::{Arc, RwLock};
+
+use bevy_utils::HashMap;
+use distill_core::{AssetTypeId, AssetUuid, TypeUuidDynamic};
+use distill_importer::{ImportedAsset, SerdeObj};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use crate::util::StableHasher;
+use crate::Asset;
+
+/// Turns an imported asset into the one that is served to the app.
+pub trait AssetProcessor<A>: Send + Sync + 'static {
+    /// Increase this when the processed assets change, so that cached ones are processed again.
+    fn version(&self) -> u32;
+
+    fn process(&self, asset: A) -> Result<A, Box<dyn Error + Send>>;
+}
+
+#[derive(Debug)]
+pub enum ProcessError {
+    /// The asset couldn't be converted to or from the serialized form passed between import and processing.
+    Serialize(AssetUuid, bincode::Error),
+    Process(AssetUuid, Box<dyn Error + Send>),
+}
+
+impl fmt::Display for ProcessError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProcessError::Serialize(id, e) => {
+                write!(
+                    f,
+                    "failed to serialize asset {:?} for processing: {}",
+                    id, e
+                )
+            }
+            ProcessError::Process(id, e) => write!(f, "failed to process asset {:?}: {}", id, e),
+        }
+    }
+}
+
+impl Error for ProcessError {}
+
+/// An [`AssetProcessor`] working on serialized assets.
+trait ErasedProcessor: Send + Sync {
+    fn version(&self) -> u32;
+
+    fn process(&self, data: &[u8]) -> Result<Vec<u8>, Box<dyn Error + Send>>;
+
+    fn deserialize(&self, data: &[u8]) -> Result<Box<dyn SerdeObj>, bincode::Error>;
+}
+
+struct TypedProcessor<A, P> {
+    processor: P,
+    marker: PhantomData<fn(A) -> A>,
+}
+
+impl<A, P> ErasedProcessor for TypedProcessor<A, P>
+where
+    A: Asset + Serialize + DeserializeOwned,
+    P: AssetProcessor<A>,
+{
+    fn version(&self) -> u32 {
+        self.processor.version()
+    }
+
+    fn process(&self, data: &[u8]) -> Result<Vec<u8>, Box<dyn Error + Send>> {
+        let asset = crate::schema::deserialize::<A>(data)?;
+        let processed = self.processor.process(asset)?;
+        bincode::serialize(&processed).map_err(|e| Box::new(e) as Box<dyn Error + Send>)
+    }
+
+    fn deserialize(&self, data: &[u8]) -> Result<Box<dyn SerdeObj>, bincode::Error> {
+        use bincode::Options;
+        let asset: A = crate::schema::bincode_options().deserialize(data)?;
+        Ok(Box::new(asset))
+    }
+}
+
+/// The processors registered through [`AddAsset::add_asset_processor`](crate::AddAsset::add_asset_processor).
+#[derive(Default)]
+pub(crate) struct ProcessorRegistry {
+    processors: RwLock<HashMap<AssetTypeId, Arc<dyn ErasedProcessor>>>,
+    cache_dir: RwLock<Option<PathBuf>>,
+}
+
+/// Shared with everything that imports assets in-process, like [`SharedSchemas`](crate::schema::SharedSchemas).
+pub(crate) type SharedProcessors = Arc<ProcessorRegistry>;
+
+impl ProcessorRegistry {
+    /// Registers `processor` for `A`, replacing the one registered before.
+    pub fn register<A, P>(&self, processor: P)
+    where
+        A: Asset + Serialize + DeserializeOwned,
+        P: AssetProcessor<A>,
+    {
+        let processor = TypedProcessor {
+            processor,
+            marker: PhantomData,
+        };
+        let previous = self
+            .processors
+            .write()
+            .unwrap()
+            .insert(AssetTypeId(*A::TYPE_UUID.as_bytes()), Arc::new(processor));
+        if previous.is_some() {
+            bevy_log::warn!(
+                "replacing the asset processor for {}",
+                std::any::type_name::<A>()
+            );
+        }
+    }
+
+    pub fn set_cache_dir(&self, cache_dir: Option<PathBuf>) {
+        *self.cache_dir.write().unwrap() = cache_dir;
+    }
+
+    /// Combines the version of an importer with the versions of the registered processors,
+    /// so the daemon imports everything again when one of them changes.
+    ///
+    /// Importers don't declare their asset types, so this covers every processor, not just the ones of the importer's types.
+    pub fn importer_version(&self, version: u32) -> u32 {
+        let processors = self.processors.read().unwrap();
+        if processors.is_empty() {
+            return version;
+        }
+        let mut versions: Vec<_> = processors
+            .iter()
+            .map(|(asset_type, processor)| (asset_type.0, processor.version()))
+            .collect();
+        versions.sort_unstable();
+
+        // written to the `.meta` files, so it has to be the same in every build
+        let mut hasher = StableHasher::new();
+        hasher.write_u32(version);
+        for (asset_type, processor_version) in versions {
+            hasher.write(&asset_type);
+            hasher.write_u32(processor_version);
+        }
+        hasher.finish_u32()
+    }
+
+    /// Runs the processors of the imported assets' types.
+    pub fn process_assets(
+        &self,
+        assets: Vec<ImportedAsset>,
+    ) -> Result<Vec<ImportedAsset>, ProcessError> {
+        assets
+            .into_iter()
+            .map(|asset| self.process_asset(asset))
+            .collect()
+    }
+
+    fn process_asset(&self, asset: ImportedAsset) -> Result<ImportedAsset, ProcessError> {
+        let asset_type = AssetTypeId(*asset.asset_data.type_uuid().as_bytes());
+        let processor = match self.processors.read().unwrap().get(&asset_type) {
+            Some(processor) => Arc::clone(processor),
+            None => return Ok(asset),
+        };
+
+        let data = bincode::serialize(&asset.asset_data)
+            .map_err(|e| ProcessError::Serialize(asset.id, e))?;
+
+        // the imported data only changes with the source, so it can stand in for the source hash
+        // names files in the cache dir, so it has to be the same in every build
+        let mut hasher = StableHasher::new();
+        hasher.write(&asset_type.0);
+        hasher.write_u32(processor.version());
+        hasher.write(&data);
+        let cache_path = self
+            .cache_dir
+            .read()
+            .unwrap()
+            .as_ref()
+            .map(|dir| dir.join(format!("{:016x}", hasher.finish())));
+
+        let cached = cache_path
+            .as_ref()
+            .and_then(|path| std::fs::read(path).ok());
+        let processed = match cached {
+            Some(processed) => processed,
+            None => {
+                let processed = processor
+                    .process(&data)
+                    .map_err(|e| ProcessError::Process(asset.id, e))?;
+                if let Some(path) = &cache_path {
+                    let written = path
+                        .parent()
+                        .map_or(Ok(()), std::fs::create_dir_all)
+                        .and_then(|()| std::fs::write(path, &processed));
+                    if let Err(e) = written {
+                        bevy_log::warn!(
+                            "failed to cache processed asset at {}: {}",
+                            path.display(),
+                            e
+                        );
+                    }
+                }
+                processed
+            }
+        };
+
+        let asset_data = processor
+            .deserialize(&processed)
+            .map_err(|e| ProcessError::Serialize(asset.id, e))?;
+        Ok(ImportedAsset {
+            asset_data,
+            ..asset
+        })
+    }
+}
@@ -0,0 +1,242 @@
+//! Versioning of serialized assets, so that old asset data stays loadable when an asset type changes.
+//!
+//! Assets are stored with bincode, which doesn't know field names, so adding a field to an asset
+//! struct breaks all data serialized before. Registering an [`AssetSchema`] through
+//! [`AddAsset::add_asset_schema`](crate::AddAsset::add_asset_schema) makes importers write the
+//! schema version in front of the data, and data of older versions is passed to the migration
+//! function of the schema when it is loaded.
+//!
+//! Data without a version, e.g. from before the schema was registered, is version `0`.
+//! Changing a schema version makes the asset daemon import all sources again, including the ones
+//! without assets of that type, because importers don't declare the asset types they produce.
+
+use std::error::Error;
+use std::fmt;
+use std::sync::{Arc, RwLock};
+
+use bevy_utils::HashMap;
+use distill_core::{AssetTypeId, TypeUuidDynamic};
+use distill_importer::{ImportedAsset, SerdeObj};
+use serde::de::DeserializeOwned;
+use serde::{Serialize, Serializer};
+
+use crate::util::StableHasher;
+use crate::Asset;
+
+/// Written in front of versioned asset data, followed by the version as little endian `u32`.
+const SCHEMA_MAGIC: [u8; 8] = *b"BASCHEMA";
+
+/// Deserializes asset data of the version `old_version`, which is older than the current one.
+pub type Migration<A> = fn(old_version: u32, data: &[u8]) -> Result<A, Box<dyn Error + Send>>;
+
+/// The schema version of an asset type, and how to load data of older versions.
+pub struct AssetSchema<A> {
+    version: u32,
+    migrate: Option<Migration<A>>,
+}
+
+impl<A> Clone for AssetSchema<A> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+impl<A> Copy for AssetSchema<A> {}
+
+impl<A> Default for AssetSchema<A> {
+    fn default() -> Self {
+        AssetSchema::new(0)
+    }
+}
+
+impl<A> fmt::Debug for AssetSchema<A> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AssetSchema")
+            .field("version", &self.version)
+            .field("migrate", &self.migrate.is_some())
+            .finish()
+    }
+}
+
+impl<A> AssetSchema<A> {
+    pub fn new(version: u32) -> Self {
+        AssetSchema {
+            version,
+            migrate: None,
+        }
+    }
+
+    /// Loads data of older versions with `migrate`. Use [`deserialize`] inside of it to read
+    /// the data into a copy of the type as it was at that version.
+    pub fn with_migration(mut self, migrate: Migration<A>) -> Self {
+        self.migrate = Some(migrate);
+        self
+    }
+
+    pub fn version(&self) -> u32 {
+        self.version
+    }
+
+    /// Deserializes `data` of `version` if it is older than the current one.
+    pub(crate) fn migrate(&self, version: u32, data: &[u8]) -> Result<A, SchemaError> {
+        if version > self.version {
+            return Err(SchemaError::NewerVersion {
+                version,
+                current: self.version,
+            });
+        }
+        let migrate = self.migrate.ok_or(SchemaError::NoMigration {
+            version,
+            current: self.version,
+        })?;
+        migrate(version, data).map_err(SchemaError::Migration)
+    }
+}
+
+#[derive(Debug)]
+pub enum SchemaError {
+    Deserialize(bincode::Error),
+    /// The data was written by a newer version of the application.
+    NewerVersion {
+        version: u32,
+        current: u32,
+    },
+    /// The data is older than the current version, but the schema has no migration.
+    NoMigration {
+        version: u32,
+        current: u32,
+    },
+    Migration(Box<dyn Error + Send>),
+}
+
+impl fmt::Display for SchemaError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SchemaError::Deserialize(e) => write!(f, "{}", e),
+            SchemaError::NewerVersion { version, current } => write!(
+                f,
+                "asset data has schema version {}, newer than the current version {}",
+                version, current
+            ),
+            SchemaError::NoMigration { version, current } => write!(
+                f,
+                "no migration from schema version {} to {}",
+                version, current
+            ),
+            SchemaError::Migration(e) => write!(f, "migration failed: {}", e),
+        }
+    }
+}
+
+impl Error for SchemaError {}
+
+pub(crate) fn bincode_options() -> impl bincode::Options {
+    use bincode::Options;
+    bincode::DefaultOptions::new()
+        .with_fixint_encoding()
+        .allow_trailing_bytes()
+}
+
+/// Deserializes asset data like the asset storage does, for reading old versions in a [`Migration`].
+pub fn deserialize<T: DeserializeOwned>(data: &[u8]) -> Result<T, Box<dyn Error + Send>> {
+    use bincode::Options;
+    bincode_options()
+        .deserialize(data)
+        .map_err(|e| Box::new(e) as Box<dyn Error + Send>)
+}
+
+/// Serializes `value` as asset data of the schema `version`, e.g. for
+/// [`MemoryAssetSource::insert_raw`](crate::MemoryAssetSource::insert_raw).
+pub fn serialize<T: Serialize>(version: u32, value: &T) -> Result<Vec<u8>, bincode::Error> {
+    bincode::serialize(&(SCHEMA_MAGIC, version, value))
+}
+
+/// Splits asset data into its schema version and the serialized asset.
+pub(crate) fn split_version(data: &[u8]) -> (u32, &[u8]) {
+    match data.strip_prefix(&SCHEMA_MAGIC[..]) {
+        Some(rest) if rest.len() >= 4 => {
+            let (version, rest) = rest.split_at(4);
+            (u32::from_le_bytes(version.try_into().unwrap()), rest)
+        }
+        _ => (0, data),
+    }
+}
+
+/// The schema versions registered through [`AddAsset::add_asset_schema`](crate::AddAsset::add_asset_schema),
+/// shared with everything that imports assets in-process.
+pub(crate) type SharedSchemas = Arc<RwLock<HashMap<AssetTypeId, u32>>>;
+
+/// Combines the version of an importer with the registered schema versions,
+/// so the daemon imports everything again when one of them changes.
+///
+/// Distill importers don't tell which asset types they produce, so this can't be limited to the schemas of their types.
+pub(crate) fn importer_version(version: u32, schemas: &SharedSchemas) -> u32 {
+    let schemas = schemas.read().unwrap();
+    if schemas.is_empty() {
+        return version;
+    }
+    let mut schemas: Vec<_> = schemas.iter().collect();
+    schemas.sort_by_key(|(asset_type, _)| asset_type.0);
+
+    // written to the `.meta` files, so it has to be the same in every build
+    let mut hasher = StableHasher::new();
+    hasher.write_u32(version);
+    for (asset_type, schema_version) in schemas {
+        hasher.write(&asset_type.0);
+        hasher.write_u32(*schema_version);
+    }
+    hasher.finish_u32()
+}
+
+/// Wraps the imported assets which have a registered schema, so they are serialized with their version.
+pub(crate) fn version_assets(
+    assets: Vec<ImportedAsset>,
+    schemas: &SharedSchemas,
+) -> Vec<ImportedAsset> {
+    let schemas = schemas.read().unwrap();
+    assets
+        .into_iter()
+        .map(|asset| {
+            let asset_type = AssetTypeId(*asset.asset_data.type_uuid().as_bytes());
+            match schemas.get(&asset_type) {
+                Some(&version) => ImportedAsset {
+                    asset_data: Box::new(VersionedAsset {
+                        version,
+                        asset: asset.asset_data,
+                    }),
+                    ..asset
+                },
+                None => asset,
+            }
+        })
+        .collect()
+}
+
+/// An imported asset that is serialized behind its schema version.
+struct VersionedAsset {
+    version: u32,
+    asset: Box<dyn SerdeObj>,
+}
+
+impl Serialize for VersionedAsset {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        (SCHEMA_MAGIC, self.version, &self.asset).serialize(serializer)
+    }
+}
+
+impl TypeUuidDynamic for VersionedAsset {
+    fn type_uuid(&self) -> bevy_reflect::Uuid {
+        self.asset.type_uuid()
+    }
+
+    fn type_name(&self) -> &'static str {
+        self.asset.type_name()
+    }
+}
+
+/// Registers the schema version of `A` for importers.
+pub(crate) fn register<A: Asset>(schemas: &SharedSchemas, schema: &AssetSchema<A>) {
+    schemas
+        .write()
+        .unwrap()
+        .insert(AssetTypeId(*A::TYPE_UUID.as_bytes()), schema.version);
+}
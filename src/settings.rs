@@ -0,0 +1,837 @@
+use std::fmt;
+#[cfg(not(target_family = "wasm"))]
+use std::path::{Path, PathBuf};
+#[cfg(not(target_family = "wasm"))]
+use std::sync::Arc;
+
+use bevy_tasks::TaskPool;
+use distill_loader::crossbeam_channel::Sender;
+use distill_loader::io::LoaderIO;
+#[cfg(not(target_family = "wasm"))]
+use serde::Deserialize;
+
+use crate::io::import::SharedImporters;
+#[cfg(feature = "packfile")]
+use crate::io::packfile_layer;
+#[cfg(not(target_family = "wasm"))]
+use crate::io::DirectoryIO;
+#[cfg(feature = "asset-daemon")]
+use crate::io::LayerIndex;
+#[cfg(feature = "asset-daemon")]
+use crate::io::MetaIndex;
+use crate::io::{AssetConnectionStatus, Layer, LayeredIO, MemoryAssetSource, MemoryIO};
+#[cfg(all(feature = "rpc-io", not(target_family = "wasm")))]
+use crate::io::{ReconnectingIO, RpcConnection};
+use crate::processor::SharedProcessors;
+use crate::schema::SharedSchemas;
+
+/// Selects where the [`AssetServer`](crate::AssetServer) loads its assets from.
+///
+/// Insert this as a resource before adding the [`AssetPlugin`](crate::AssetPlugin).
+/// If it is missing, a default is chosen based on the enabled features.
+#[derive(Debug, Clone)]
+pub enum AssetServerSettings {
+    /// Start an asset daemon in-process and connect to it over TCP.
+    #[cfg(feature = "asset-daemon")]
+    Daemon(AssetDaemonSettings),
+    /// Connect to an already running asset daemon over TCP.
+    #[cfg(all(feature = "rpc-io", not(target_family = "wasm")))]
+    DaemonTcp(AssetDaemonTcpSettings),
+    /// Connect to an already running asset daemon over a websocket.
+    #[cfg(feature = "rpc-io")]
+    DaemonWebsocket(AssetDaemonWebsocketSettings),
+    #[cfg(feature = "packfile")]
+    Packfile(PackfileSettings),
+    /// Import the files of a directory in-process, without a daemon or database.
+    #[cfg(not(target_family = "wasm"))]
+    Directory(DirectorySettings),
+    /// Serve assets inserted into a [`MemoryAssetSource`], mostly useful for tests.
+    InMemory(MemoryAssetSource),
+    /// Combine multiple sources, ordered by priority: the first one is the base, and each asset
+    /// is loaded from the last source that has it.
+    ///
+    /// Only the base can be a source which doesn't know its contents up front,
+    /// like a connection to a daemon running in another process. A [`Daemon`](Self::Daemon) started in-process
+    /// is indexed by the `.meta` files in its asset dirs, which are read in the background.
+    Layered(Vec<AssetServerSettings>),
+}
+
+/// Everything the [`AssetPlugin`](crate::AssetPlugin) provides for creating the [`LoaderIO`].
+#[derive(Clone)]
+pub(crate) struct LoaderIOContext {
+    /// Receives the [`AssetConnectionStatus`] of connections to a daemon.
+    pub status_sender: Sender<AssetConnectionStatus>,
+    pub importers: SharedImporters,
+    pub schemas: SharedSchemas,
+    pub processors: SharedProcessors,
+    pub task_pool: TaskPool,
+}
+
+impl AssetServerSettings {
+    pub(crate) fn default_fallback() -> Option<Self> {
+        #[cfg(feature = "asset-daemon")]
+        return Some(AssetServerSettings::Daemon(AssetDaemonSettings::default()));
+        #[cfg(all(
+            not(feature = "asset-daemon"),
+            feature = "rpc-io",
+            not(target_family = "wasm")
+        ))]
+        return Some(AssetServerSettings::DaemonTcp(
+            AssetDaemonTcpSettings::default(),
+        ));
+        #[cfg(all(
+            not(feature = "asset-daemon"),
+            feature = "rpc-io",
+            target_family = "wasm"
+        ))]
+        return Some(AssetServerSettings::DaemonWebsocket(
+            AssetDaemonWebsocketSettings::default(),
+        ));
+        #[cfg(not(any(feature = "asset-daemon", feature = "rpc-io")))]
+        return None;
+    }
+
+    #[cfg(feature = "asset-daemon")]
+    pub(crate) fn daemon_settings(&self) -> Option<&AssetDaemonSettings> {
+        match self {
+            AssetServerSettings::Daemon(settings) => Some(settings),
+            AssetServerSettings::Layered(layers) => {
+                layers.iter().find_map(AssetServerSettings::daemon_settings)
+            }
+            #[allow(unreachable_patterns)]
+            _ => None,
+        }
+    }
+
+    #[cfg(not(feature = "packfile"))]
+    pub(crate) fn loader_io(
+        &self,
+        context: &LoaderIOContext,
+    ) -> Result<Box<dyn LoaderIO>, Box<dyn std::error::Error>> {
+        self.layer(context, false).map(|layer| layer.io)
+    }
+
+    /// Like [`loader_io`](Self::loader_io), but always layered so packfiles can be mounted on top.
+    #[cfg(feature = "packfile")]
+    pub(crate) fn layered_io(
+        &self,
+        context: &LoaderIOContext,
+    ) -> Result<LayeredIO, Box<dyn std::error::Error>> {
+        match *self {
+            AssetServerSettings::Layered(ref layers) => {
+                Ok(LayeredIO::new(Self::layers(layers, context)?))
+            }
+            _ => Ok(LayeredIO::new(vec![self.layer(context, false)?])),
+        }
+    }
+
+    fn layers(
+        layers: &[AssetServerSettings],
+        context: &LoaderIOContext,
+    ) -> Result<Vec<Layer>, Box<dyn std::error::Error>> {
+        if layers.is_empty() {
+            return Err(Box::new(AssetSettingsError::NoLayers));
+        }
+        let stacked = layers.len() > 1;
+        let layers = layers
+            .iter()
+            .map(|settings| settings.layer(context, stacked))
+            .collect::<Result<Vec<_>, _>>()?;
+        if let Some(i) = layers
+            .iter()
+            .skip(1)
+            .position(|layer| layer.index.is_none())
+        {
+            return Err(Box::new(AssetSettingsError::LayerCannotShadow(i + 1)));
+        }
+        Ok(layers)
+    }
+
+    /// Creates the source as a layer of a [`LayeredIO`].
+    ///
+    /// Sources which need extra work to know their contents, like a daemon, only get an index
+    /// when they are `stacked` with other layers.
+    fn layer(
+        &self,
+        #[allow(unused_variables)] context: &LoaderIOContext,
+        #[allow(unused_variables)] stacked: bool,
+    ) -> Result<Layer, Box<dyn std::error::Error>> {
+        let unindexed = |io: Box<dyn LoaderIO>| Layer { io, index: None };
+
+        match *self {
+            #[cfg(feature = "asset-daemon")]
+            AssetServerSettings::Daemon(ref settings) => Ok(Layer {
+                io: Box::new(ReconnectingIO::new(
+                    settings.address,
+                    RpcConnection::Tcp,
+                    context.status_sender.clone(),
+                )),
+                index: stacked
+                    .then(|| MetaIndex::watch(settings.asset_dirs.clone()) as Arc<dyn LayerIndex>),
+            }),
+            #[cfg(all(feature = "rpc-io", not(target_family = "wasm")))]
+            AssetServerSettings::DaemonTcp(ref settings) => {
+                Ok(unindexed(Box::new(ReconnectingIO::new(
+                    settings.address,
+                    RpcConnection::Tcp,
+                    context.status_sender.clone(),
+                ))))
+            }
+            #[cfg(all(feature = "rpc-io", not(target_family = "wasm")))]
+            AssetServerSettings::DaemonWebsocket(ref settings) => {
+                Ok(unindexed(Box::new(ReconnectingIO::new(
+                    settings.address,
+                    RpcConnection::Websocket,
+                    context.status_sender.clone(),
+                ))))
+            }
+            #[cfg(all(feature = "rpc-io", target_family = "wasm"))]
+            AssetServerSettings::DaemonWebsocket(ref settings) => Ok(unindexed(Box::new(
+                distill_loader::RpcIO::new(distill_loader::rpc_io::RpcConnectionType::Websocket(
+                    settings.address.to_string(),
+                ))?,
+            ))),
+            #[cfg(feature = "packfile")]
+            AssetServerSettings::Packfile(ref settings) => packfile_layer(settings)
+                .map(|(layer, _)| layer)
+                .map_err(Into::into),
+            #[cfg(not(target_family = "wasm"))]
+            AssetServerSettings::Directory(ref settings) => {
+                let io = DirectoryIO::new(
+                    settings.root.clone(),
+                    settings.watch,
+                    settings.deterministic_uuids,
+                    Arc::clone(&context.importers),
+                    Arc::clone(&context.schemas),
+                    settings.process.then(|| Arc::clone(&context.processors)),
+                    context.task_pool.clone(),
+                );
+                let index = io.contents();
+                Ok(Layer {
+                    io: Box::new(io),
+                    index: Some(index),
+                })
+            }
+            AssetServerSettings::InMemory(ref source) => Ok(Layer {
+                io: Box::new(MemoryIO::new(source.clone())),
+                index: Some(source.contents()),
+            }),
+            AssetServerSettings::Layered(ref layers) => Ok(unindexed(Box::new(LayeredIO::new(
+                Self::layers(layers, context)?,
+            )))),
+        }
+    }
+}
+
+/// Limits how many assets finish loading per frame, which spreads committing the assets of a large
+/// level across frames.
+///
+/// Assets are deserialized on the [`AsyncComputeTaskPool`](bevy_tasks::AsyncComputeTaskPool) right away,
+/// the budget applies to handing the deserialized assets to their [`Assets`](crate::Assets) on the main thread.
+/// Insert this as a resource before adding the [`AssetPlugin`](crate::AssetPlugin). Assets over the budget
+/// stay in the [`Loading`](distill_loader::storage::LoadStatus::Loading) state until a later frame, assets with a
+/// higher [load priority](crate::AssetServer::set_load_priority) are handled first.
+/// At least one asset is handled every frame, so loading always makes progress.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AssetLoadBudget {
+    Unlimited,
+    /// Stop once handling assets took this long on the main thread in the frame,
+    /// including committing the assets handed over in the previous frame.
+    Time(std::time::Duration),
+    /// Handle at most this many assets per frame.
+    Assets(usize),
+}
+
+impl AssetLoadBudget {
+    pub fn from_millis(millis: u64) -> Self {
+        AssetLoadBudget::Time(std::time::Duration::from_millis(millis))
+    }
+}
+
+impl Default for AssetLoadBudget {
+    fn default() -> Self {
+        AssetLoadBudget::Unlimited
+    }
+}
+
+#[cfg(feature = "packfile")]
+#[derive(Debug, Clone)]
+pub enum PackfileSettings {
+    #[cfg(not(target_family = "wasm"))]
+    Path(std::path::PathBuf),
+    Static(&'static [u8]),
+}
+
+/// Settings for loading assets directly from a directory.
+///
+/// The importers registered through [`AddAsset`](crate::AddAsset) run inside of the app.
+/// UUIDs are read from existing `.meta` files, but no `.meta` files are written.
+#[cfg(not(target_family = "wasm"))]
+#[derive(Debug, Clone)]
+pub struct DirectorySettings {
+    root: PathBuf,
+    watch: bool,
+    deterministic_uuids: bool,
+    process: bool,
+}
+
+#[cfg(not(target_family = "wasm"))]
+impl Default for DirectorySettings {
+    fn default() -> Self {
+        DirectorySettings::new("assets")
+    }
+}
+
+#[cfg(not(target_family = "wasm"))]
+impl DirectorySettings {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        DirectorySettings {
+            root: root.into(),
+            watch: false,
+            deterministic_uuids: false,
+            process: true,
+        }
+    }
+
+    /// Periodically reimport changed files and reload the affected assets.
+    pub fn with_watch(mut self, watch: bool) -> Self {
+        self.watch = watch;
+        self
+    }
+
+    /// Derive the UUIDs of assets without a `.meta` file from their path, instead of generating
+    /// new ones on every import. See [`deterministic_asset_uuid`](crate::util::deterministic_asset_uuid).
+    ///
+    /// Daemons have no such setting, they always write random UUIDs to the `.meta` files they create.
+    pub fn with_deterministic_uuids(mut self, deterministic_uuids: bool) -> Self {
+        self.deterministic_uuids = deterministic_uuids;
+        self
+    }
+
+    /// Serve assets processed by the [`AssetProcessor`](crate::processor::AssetProcessor)s registered
+    /// through [`AddAsset`](crate::AddAsset) instead of the raw imported ones. Enabled by default.
+    pub fn with_processing(mut self, process: bool) -> Self {
+        self.process = process;
+        self
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+}
+
+/// Errors returned when building or loading asset server settings.
+#[derive(Debug)]
+pub enum AssetSettingsError {
+    NoAssetDirs,
+    NoLayers,
+    /// The layer at this index can't tell which assets it contains, so it can only be the base layer.
+    LayerCannotShadow(usize),
+    AssetDirNotADirectory(std::path::PathBuf),
+    EmptyDbPath,
+    DbPathInsideAssetDir {
+        db_path: std::path::PathBuf,
+        asset_dir: std::path::PathBuf,
+    },
+    InvalidEnvVar {
+        name: &'static str,
+        value: String,
+    },
+    Io(std::io::Error),
+    #[cfg(all(feature = "rpc-io", not(target_family = "wasm")))]
+    Config(ron::Error),
+}
+
+impl fmt::Display for AssetSettingsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AssetSettingsError::NoAssetDirs => write!(f, "no asset directories configured"),
+            AssetSettingsError::NoLayers => write!(f, "layered asset source without layers"),
+            AssetSettingsError::LayerCannotShadow(i) => write!(
+                f,
+                "asset source layer {} can't shadow other layers, only the first layer can be a remote daemon",
+                i
+            ),
+            AssetSettingsError::AssetDirNotADirectory(path) => {
+                write!(f, "asset dir `{}` is not a directory", path.display())
+            }
+            AssetSettingsError::EmptyDbPath => write!(f, "the asset database path is empty"),
+            AssetSettingsError::DbPathInsideAssetDir { db_path, asset_dir } => write!(
+                f,
+                "asset database `{}` is inside the asset dir `{}`",
+                db_path.display(),
+                asset_dir.display()
+            ),
+            AssetSettingsError::InvalidEnvVar { name, value } => {
+                write!(f, "invalid value `{}` for environment variable {}", value, name)
+            }
+            AssetSettingsError::Io(e) => write!(f, "failed to read settings: {}", e),
+            #[cfg(all(feature = "rpc-io", not(target_family = "wasm")))]
+            AssetSettingsError::Config(e) => write!(f, "invalid settings file: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for AssetSettingsError {}
+
+impl From<std::io::Error> for AssetSettingsError {
+    fn from(e: std::io::Error) -> Self {
+        AssetSettingsError::Io(e)
+    }
+}
+
+#[cfg(all(
+    any(feature = "rpc-io", feature = "asset-daemon"),
+    not(target_family = "wasm")
+))]
+fn env_var<T: std::str::FromStr>(name: &'static str) -> Result<Option<T>, AssetSettingsError> {
+    match std::env::var(name) {
+        Ok(value) => value
+            .parse()
+            .map(Some)
+            .map_err(|_| AssetSettingsError::InvalidEnvVar { name, value }),
+        Err(_) => Ok(None),
+    }
+}
+
+#[cfg(feature = "asset-daemon")]
+fn env_flag(name: &'static str) -> Result<Option<bool>, AssetSettingsError> {
+    match std::env::var(name) {
+        Ok(value) => match value.as_str() {
+            "1" | "true" | "yes" => Ok(Some(true)),
+            "0" | "false" | "no" => Ok(Some(false)),
+            _ => Err(AssetSettingsError::InvalidEnvVar { name, value }),
+        },
+        Err(_) => Ok(None),
+    }
+}
+
+#[cfg(all(feature = "rpc-io", not(target_family = "wasm")))]
+fn read_config<T: for<'de> Deserialize<'de>>(path: &Path) -> Result<T, AssetSettingsError> {
+    let contents = std::fs::read_to_string(path)?;
+    ron::de::from_str(&contents).map_err(AssetSettingsError::Config)
+}
+
+/// Settings for the asset daemon started by the [`AssetPlugin`](crate::AssetPlugin).
+///
+/// Use [`AssetDaemonSettings::builder`] to change the defaults.
+/// Deserialized settings are validated like the ones returned by [`AssetDaemonSettingsBuilder::build`].
+#[cfg(feature = "asset-daemon")]
+#[derive(Debug, Clone, Deserialize)]
+// only the builder deserializes the fields directly, see the `Deserialize` impl below
+#[serde(default, remote = "Self")]
+pub struct AssetDaemonSettings {
+    asset_dirs: Vec<PathBuf>,
+    db_path: PathBuf,
+    address: std::net::SocketAddr,
+    clear_db_on_start: bool,
+    process_assets: bool,
+}
+
+#[cfg(feature = "asset-daemon")]
+impl Default for AssetDaemonSettings {
+    fn default() -> Self {
+        AssetDaemonSettingsBuilder::default().settings
+    }
+}
+
+#[cfg(feature = "asset-daemon")]
+impl<'de> Deserialize<'de> for AssetDaemonSettings {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        AssetDaemonSettingsBuilder::deserialize(deserializer)?
+            .build()
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(feature = "asset-daemon")]
+impl AssetDaemonSettings {
+    pub const ASSET_DIRS_VAR: &'static str = "BEVY_ASSET_DIRS";
+    pub const DB_PATH_VAR: &'static str = "BEVY_ASSET_DB_PATH";
+    pub const ADDRESS_VAR: &'static str = AssetDaemonTcpSettings::ADDRESS_VAR;
+    pub const CLEAR_DB_VAR: &'static str = "BEVY_ASSET_CLEAR_DB";
+    pub const PROCESS_ASSETS_VAR: &'static str = "BEVY_ASSET_PROCESS";
+
+    pub fn builder() -> AssetDaemonSettingsBuilder {
+        AssetDaemonSettingsBuilder::default()
+    }
+
+    /// Reads the settings from `BEVY_ASSET_DIRS` (a list of paths separated like `PATH`),
+    /// `BEVY_ASSET_DB_PATH`, `BEVY_ASSET_DAEMON_ADDRESS`, `BEVY_ASSET_CLEAR_DB` and `BEVY_ASSET_PROCESS`.
+    /// Unset variables keep their default value.
+    pub fn from_env() -> Result<Self, AssetSettingsError> {
+        AssetDaemonSettingsBuilder::default().env()?.build()
+    }
+
+    /// Reads the settings from a RON file. Missing fields keep their default value.
+    pub fn from_config_file(path: impl AsRef<Path>) -> Result<Self, AssetSettingsError> {
+        read_config::<AssetDaemonSettingsBuilder>(path.as_ref())?.build()
+    }
+
+    pub(crate) fn daemon(
+        &self,
+        asset_loaders: Vec<(
+            &'static [&'static str],
+            Box<dyn distill_importer::BoxedImporter>,
+        )>,
+    ) -> distill_daemon::AssetDaemon {
+        let mut asset_daemon = distill_daemon::AssetDaemon::default()
+            .with_db_path(self.db_path.clone())
+            .with_address(self.address)
+            .with_importers_boxed(asset_loaders)
+            .with_asset_dirs(self.asset_dirs.clone());
+        if self.clear_db_on_start {
+            asset_daemon = asset_daemon.with_clear_db_on_start();
+        }
+        asset_daemon
+    }
+
+    pub fn asset_dirs(&self) -> &[PathBuf] {
+        &self.asset_dirs
+    }
+    pub fn db_path(&self) -> &Path {
+        &self.db_path
+    }
+    pub fn address(&self) -> std::net::SocketAddr {
+        self.address
+    }
+    pub fn clear_db_on_start(&self) -> bool {
+        self.clear_db_on_start
+    }
+    pub fn process_assets(&self) -> bool {
+        self.process_assets
+    }
+}
+
+#[cfg(feature = "asset-daemon")]
+#[derive(Debug, Clone, Deserialize)]
+#[serde(transparent)]
+pub struct AssetDaemonSettingsBuilder {
+    #[serde(with = "AssetDaemonSettings")]
+    settings: AssetDaemonSettings,
+}
+
+#[cfg(feature = "asset-daemon")]
+impl Default for AssetDaemonSettingsBuilder {
+    fn default() -> Self {
+        AssetDaemonSettingsBuilder {
+            settings: AssetDaemonSettings {
+                asset_dirs: vec![PathBuf::from("assets")],
+                db_path: PathBuf::from(".assets_db"),
+                address: ([127, 0, 0, 1], 9999).into(),
+                clear_db_on_start: false,
+                process_assets: true,
+            },
+        }
+    }
+}
+
+#[cfg(feature = "asset-daemon")]
+impl AssetDaemonSettingsBuilder {
+    /// Replaces the asset directories with a single one.
+    pub fn asset_dir(mut self, asset_dir: impl Into<PathBuf>) -> Self {
+        self.settings.asset_dirs = vec![asset_dir.into()];
+        self
+    }
+    pub fn asset_dirs(mut self, asset_dirs: impl IntoIterator<Item = impl Into<PathBuf>>) -> Self {
+        self.settings.asset_dirs = asset_dirs.into_iter().map(Into::into).collect();
+        self
+    }
+    pub fn add_asset_dir(mut self, asset_dir: impl Into<PathBuf>) -> Self {
+        self.settings.asset_dirs.push(asset_dir.into());
+        self
+    }
+    pub fn db_path(mut self, db_path: impl Into<PathBuf>) -> Self {
+        self.settings.db_path = db_path.into();
+        self
+    }
+    pub fn address(mut self, address: impl Into<std::net::SocketAddr>) -> Self {
+        self.settings.address = address.into();
+        self
+    }
+    pub fn port(mut self, port: u16) -> Self {
+        self.settings.address.set_port(port);
+        self
+    }
+    pub fn clear_db_on_start(mut self, clear_db_on_start: bool) -> Self {
+        self.settings.clear_db_on_start = clear_db_on_start;
+        self
+    }
+    /// Serve assets processed by the [`AssetProcessor`](crate::processor::AssetProcessor)s registered
+    /// through [`AddAsset`](crate::AddAsset) instead of the raw imported ones. Enabled by default.
+    pub fn process_assets(mut self, process_assets: bool) -> Self {
+        self.settings.process_assets = process_assets;
+        self
+    }
+
+    /// Overrides the current values with the ones set in the environment.
+    /// See [`AssetDaemonSettings::from_env`].
+    pub fn env(mut self) -> Result<Self, AssetSettingsError> {
+        if let Some(asset_dirs) = std::env::var_os(AssetDaemonSettings::ASSET_DIRS_VAR) {
+            self.settings.asset_dirs = std::env::split_paths(&asset_dirs).collect();
+        }
+        if let Some(db_path) = std::env::var_os(AssetDaemonSettings::DB_PATH_VAR) {
+            self.settings.db_path = PathBuf::from(db_path);
+        }
+        if let Some(address) = env_var(AssetDaemonSettings::ADDRESS_VAR)? {
+            self.settings.address = address;
+        }
+        if let Some(clear_db_on_start) = env_flag(AssetDaemonSettings::CLEAR_DB_VAR)? {
+            self.settings.clear_db_on_start = clear_db_on_start;
+        }
+        if let Some(process_assets) = env_flag(AssetDaemonSettings::PROCESS_ASSETS_VAR)? {
+            self.settings.process_assets = process_assets;
+        }
+        Ok(self)
+    }
+
+    pub fn build(self) -> Result<AssetDaemonSettings, AssetSettingsError> {
+        let settings = self.settings;
+
+        if settings.asset_dirs.is_empty() {
+            return Err(AssetSettingsError::NoAssetDirs);
+        }
+        if settings.db_path.as_os_str().is_empty() {
+            return Err(AssetSettingsError::EmptyDbPath);
+        }
+        for asset_dir in &settings.asset_dirs {
+            if asset_dir.exists() && !asset_dir.is_dir() {
+                return Err(AssetSettingsError::AssetDirNotADirectory(asset_dir.clone()));
+            }
+            // the daemon watches its asset dirs, so a database inside of one would trigger itself
+            if settings.db_path.starts_with(asset_dir) {
+                return Err(AssetSettingsError::DbPathInsideAssetDir {
+                    db_path: settings.db_path.clone(),
+                    asset_dir: asset_dir.clone(),
+                });
+            }
+        }
+
+        Ok(settings)
+    }
+}
+
+/// Settings for connecting to an already running asset daemon over TCP.
+#[cfg(all(feature = "rpc-io", not(target_family = "wasm")))]
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct AssetDaemonTcpSettings {
+    address: std::net::SocketAddr,
+}
+
+#[cfg(all(feature = "rpc-io", not(target_family = "wasm")))]
+impl Default for AssetDaemonTcpSettings {
+    fn default() -> Self {
+        AssetDaemonTcpSettings {
+            address: ([127, 0, 0, 1], 9999).into(),
+        }
+    }
+}
+
+#[cfg(all(feature = "rpc-io", not(target_family = "wasm")))]
+impl AssetDaemonTcpSettings {
+    pub const ADDRESS_VAR: &'static str = "BEVY_ASSET_DAEMON_ADDRESS";
+
+    pub fn new(address: impl Into<std::net::SocketAddr>) -> Self {
+        AssetDaemonTcpSettings {
+            address: address.into(),
+        }
+    }
+
+    /// Reads the address from `BEVY_ASSET_DAEMON_ADDRESS`, falling back to the default.
+    pub fn from_env() -> Result<Self, AssetSettingsError> {
+        let mut settings = AssetDaemonTcpSettings::default();
+        if let Some(address) = env_var(Self::ADDRESS_VAR)? {
+            settings.address = address;
+        }
+        Ok(settings)
+    }
+
+    pub fn from_config_file(path: impl AsRef<Path>) -> Result<Self, AssetSettingsError> {
+        read_config(path.as_ref())
+    }
+
+    pub fn with_port(mut self, port: u16) -> Self {
+        self.address.set_port(port);
+        self
+    }
+
+    pub fn address(&self) -> std::net::SocketAddr {
+        self.address
+    }
+}
+
+/// Settings for connecting to an already running asset daemon over a websocket.
+#[cfg(feature = "rpc-io")]
+#[derive(Debug, Clone)]
+#[cfg_attr(not(target_family = "wasm"), derive(Deserialize), serde(default))]
+pub struct AssetDaemonWebsocketSettings {
+    address: std::net::SocketAddr,
+}
+
+#[cfg(feature = "rpc-io")]
+impl Default for AssetDaemonWebsocketSettings {
+    fn default() -> Self {
+        AssetDaemonWebsocketSettings {
+            address: ([127, 0, 0, 1], 9998).into(),
+        }
+    }
+}
+
+#[cfg(feature = "rpc-io")]
+impl AssetDaemonWebsocketSettings {
+    pub const ADDRESS_VAR: &'static str = "BEVY_ASSET_DAEMON_WEBSOCKET_ADDRESS";
+
+    pub fn new(address: impl Into<std::net::SocketAddr>) -> Self {
+        AssetDaemonWebsocketSettings {
+            address: address.into(),
+        }
+    }
+
+    /// Reads the address from `BEVY_ASSET_DAEMON_WEBSOCKET_ADDRESS`, falling back to the default.
+    #[cfg(not(target_family = "wasm"))]
+    pub fn from_env() -> Result<Self, AssetSettingsError> {
+        let mut settings = AssetDaemonWebsocketSettings::default();
+        if let Some(address) = env_var(Self::ADDRESS_VAR)? {
+            settings.address = address;
+        }
+        Ok(settings)
+    }
+
+    #[cfg(not(target_family = "wasm"))]
+    pub fn from_config_file(path: impl AsRef<Path>) -> Result<Self, AssetSettingsError> {
+        read_config(path.as_ref())
+    }
+
+    pub fn with_port(mut self, port: u16) -> Self {
+        self.address.set_port(port);
+        self
+    }
+
+    pub fn address(&self) -> std::net::SocketAddr {
+        self.address
+    }
+}
+
+#[cfg(all(test, feature = "asset-daemon"))]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "bevy_asset_settings_{}_{}",
+            name,
+            std::process::id()
+        ));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn build_rejects_invalid_paths() {
+        assert!(matches!(
+            AssetDaemonSettings::builder()
+                .asset_dirs(Vec::<PathBuf>::new())
+                .build(),
+            Err(AssetSettingsError::NoAssetDirs)
+        ));
+        assert!(matches!(
+            AssetDaemonSettings::builder().db_path("").build(),
+            Err(AssetSettingsError::EmptyDbPath)
+        ));
+        assert!(matches!(
+            AssetDaemonSettings::builder()
+                .asset_dir("assets")
+                .db_path("assets/.assets_db")
+                .build(),
+            Err(AssetSettingsError::DbPathInsideAssetDir { .. })
+        ));
+
+        let dir = temp_dir("not_a_dir");
+        let file = dir.join("file");
+        std::fs::write(&file, "").unwrap();
+        assert!(matches!(
+            AssetDaemonSettings::builder().asset_dir(&file).build(),
+            Err(AssetSettingsError::AssetDirNotADirectory(path)) if path == file
+        ));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn config_file_is_validated() {
+        let dir = temp_dir("config");
+        let path = dir.join("settings.ron");
+
+        std::fs::write(&path, r#"(address: "127.0.0.1:4000", db_path: "db")"#).unwrap();
+        let settings = AssetDaemonSettings::from_config_file(&path).unwrap();
+        assert_eq!(settings.address(), ([127, 0, 0, 1], 4000).into());
+        assert_eq!(settings.db_path(), Path::new("db"));
+        assert_eq!(settings.asset_dirs(), [PathBuf::from("assets")]);
+
+        for invalid in [
+            r#"(address: "localhost")"#,
+            r#"(address: "127.0.0.1:70000")"#,
+            r#"(db_path: "")"#,
+            r#"(asset_dirs: [])"#,
+        ] {
+            std::fs::write(&path, invalid).unwrap();
+            assert!(
+                matches!(
+                    AssetDaemonSettings::from_config_file(&path),
+                    Err(AssetSettingsError::Config(_))
+                ),
+                "accepted {}",
+                invalid
+            );
+        }
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    // the only test touching the environment, since it is shared by all tests
+    #[test]
+    fn env_vars_are_validated() {
+        let vars = [
+            AssetDaemonSettings::ASSET_DIRS_VAR,
+            AssetDaemonSettings::DB_PATH_VAR,
+            AssetDaemonSettings::ADDRESS_VAR,
+            AssetDaemonSettings::CLEAR_DB_VAR,
+            AssetDaemonSettings::PROCESS_ASSETS_VAR,
+        ];
+        let from_env_with = |name: &'static str, value: &str| {
+            vars.iter().for_each(std::env::remove_var);
+            std::env::set_var(name, value);
+            let result = AssetDaemonSettings::from_env();
+            std::env::remove_var(name);
+            result
+        };
+
+        for address in [
+            "localhost",
+            "127.0.0.1",
+            "127.0.0.1:70000",
+            "127.0.0.1:port",
+        ] {
+            assert!(matches!(
+                from_env_with(AssetDaemonSettings::ADDRESS_VAR, address),
+                Err(AssetSettingsError::InvalidEnvVar { name, value })
+                    if name == AssetDaemonSettings::ADDRESS_VAR && value == address
+            ));
+        }
+        assert!(matches!(
+            from_env_with(AssetDaemonSettings::CLEAR_DB_VAR, "maybe"),
+            Err(AssetSettingsError::InvalidEnvVar { .. })
+        ));
+        assert!(matches!(
+            from_env_with(AssetDaemonSettings::DB_PATH_VAR, ""),
+            Err(AssetSettingsError::EmptyDbPath)
+        ));
+        assert!(matches!(
+            from_env_with(AssetDaemonSettings::DB_PATH_VAR, "assets/db"),
+            Err(AssetSettingsError::DbPathInsideAssetDir { .. })
+        ));
+
+        let settings = from_env_with(AssetDaemonSettings::ADDRESS_VAR, "127.0.0.1:4000").unwrap();
+        assert_eq!(settings.address(), ([127, 0, 0, 1], 4000).into());
+    }
+}
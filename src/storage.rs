@@ -1,20 +1,24 @@
 use std::error::Error;
-use std::sync::Arc;
+use std::sync::{Arc, RwLock};
+use std::time::Instant;
 
 use bevy_app::Events;
 use bevy_ecs::prelude::*;
 
+use bevy_tasks::AsyncComputeTaskPool;
 use bevy_utils::HashMap;
-use distill_loader::crossbeam_channel::Sender;
+use distill_core::{AssetRef, AssetUuid};
+use distill_loader::crossbeam_channel::{unbounded, Receiver, Sender};
 use distill_loader::handle::{AssetHandle, RefOp, TypedAssetStorage};
 use distill_loader::storage::{
     AssetLoadOp, AssetStorage, HandleAllocator, IndirectionTable, LoadHandle, LoaderInfoProvider,
 };
-use distill_loader::AssetTypeId;
+use distill_loader::{AssetTypeId, Loader};
 use serde::de::DeserializeSeed;
 
 use crate::prelude::{Handle, WeakHandle};
-use crate::AssetEvent;
+use crate::schema::{self, AssetSchema, SchemaError};
+use crate::{AssetEvent, AssetLoadBudget};
 
 use super::Asset;
 
@@ -22,13 +26,23 @@ struct AssetState<A> {
     version: u32,
     asset: A,
 }
+
+/// An asset deserialized on the [`AsyncComputeTaskPool`], waiting to be committed.
+struct DeserializedAsset<A> {
+    load_handle: LoadHandle,
+    state: AssetState<A>,
+}
+
 pub struct Assets<A: Asset> {
     refop_sender: Arc<Sender<RefOp>>,
     handle_allocator: Arc<dyn HandleAllocator>,
     assets: HashMap<LoadHandle, AssetState<A>>,
-    uncommitted: HashMap<LoadHandle, AssetState<A>>,
+    /// Keyed by version as well, a reload can finish deserializing before an older version is committed.
+    uncommitted: HashMap<(LoadHandle, u32), A>,
+    deserialized: (Sender<DeserializedAsset<A>>, Receiver<DeserializedAsset<A>>),
     indirection_table: IndirectionTable,
     events: Events<AssetEvent<A>>,
+    schema: AssetSchema<A>,
 }
 impl<A: Asset> Assets<A> {
     pub fn new(
@@ -41,11 +55,17 @@ impl<A: Asset> Assets<A> {
             handle_allocator,
             assets: HashMap::default(),
             uncommitted: HashMap::default(),
+            deserialized: unbounded(),
             indirection_table,
             events: Events::default(),
+            schema: AssetSchema::default(),
         }
     }
 
+    pub(crate) fn set_schema(&mut self, schema: AssetSchema<A>) {
+        self.schema = schema;
+    }
+
     pub fn get<T: AssetHandle>(&self, handle: &T) -> Option<&A> {
         let handle = self.resolve_handle(handle.load_handle())?;
         self.assets.get(&handle).map(|a| &a.asset)
@@ -123,6 +143,50 @@ impl<A: Asset> Assets<A> {
         Some(WeakHandle::new(load_handle))
     }
 
+    /// Moves the assets deserialized by tasks so far to the uncommitted assets.
+    ///
+    /// Versions which finished deserializing after a newer version was committed are dropped.
+    fn receive_deserialized(&mut self) {
+        for deserialized in self.deserialized.1.try_iter() {
+            let AssetState { version, asset } = deserialized.state;
+            let load_handle = deserialized.load_handle;
+            match self.assets.get(&load_handle) {
+                Some(committed) if committed.version >= version => {}
+                _ => {
+                    self.uncommitted.insert((load_handle, version), asset);
+                }
+            }
+        }
+    }
+
+    fn commit(&mut self, load_handle: LoadHandle, version: u32) {
+        self.receive_deserialized();
+
+        // The commit step is done after an asset load has completed.
+        // It exists to avoid frames where an asset that was loaded is unloaded, which
+        // could happen when hot reloading. To support this case, you must support having multiple
+        // versions of an asset loaded at the same time.
+        let asset = match self.uncommitted.remove(&(load_handle, version)) {
+            Some(asset) => asset,
+            None => {
+                bevy_log::error!(
+                    "asset {:?}@{} not present when committing",
+                    load_handle,
+                    version
+                );
+                return;
+            }
+        };
+        // older versions were superseded before they were committed
+        self.uncommitted
+            .retain(|&(handle, v), _| handle != load_handle || v > version);
+        self.assets
+            .insert(load_handle, AssetState { version, asset });
+
+        let handle = WeakHandle::new(load_handle);
+        self.events.send(AssetEvent::Modified { handle, version });
+    }
+
     fn resolve_handle(&self, load_handle: LoadHandle) -> Option<LoadHandle> {
         if load_handle.is_indirect() {
             self.indirection_table.resolve(load_handle)
@@ -160,16 +224,19 @@ impl<A: Asset> TypedAssetStorage<A> for Assets<A> {
 struct AssetStorageData<'a, A: Asset, D> {
     seed: D,
     assets: &'a mut Assets<A>,
+    task_pool: AsyncComputeTaskPool,
+    load_handles: LoadHandleTable,
+    deserialized_updates: Sender<DeserializeResult>,
 }
 
 impl<'a, A, D> AssetStorage for AssetStorageData<'a, A, D>
 where
     A: Asset,
-    D: FromWorld + Clone + for<'de> DeserializeSeed<'de, Value = A>,
+    D: FromWorld + Clone + Send + for<'de> DeserializeSeed<'de, Value = A> + 'static,
 {
     fn update_asset(
         &mut self,
-        loader_info: &dyn LoaderInfoProvider,
+        _loader_info: &dyn LoaderInfoProvider,
         asset_type: &AssetTypeId,
         data: Vec<u8>,
         load_handle: LoadHandle,
@@ -178,39 +245,61 @@ where
     ) -> Result<(), Box<dyn Error + Send + 'static>> {
         debug_assert_eq!(*A::TYPE_UUID.as_bytes(), asset_type.0);
 
-        use bincode::Options;
-        let bincode = bincode::DefaultOptions::new()
-            .with_fixint_encoding()
-            .allow_trailing_bytes();
-
         let seed = self.seed.clone();
-
-        // To enable automatic serde of Handle, we need to set up a SerdeContext with a RefOp sender
-        let asset = futures_executor::block_on(distill_loader::handle::SerdeContext::with(
-            loader_info,
-            (*self.assets.refop_sender).clone(),
-            async { bincode.deserialize_seed::<D>(seed, &data) },
-        ));
-        let asset = match asset {
-            Ok(asset) => asset,
-            Err(e) => {
-                load_op.error(e);
-                return Ok(());
-            }
-        };
-
-        self.assets
-            .uncommitted
-            .insert(load_handle, AssetState { version, asset });
-        load_op.complete();
-
-        bevy_log::trace!(
-            "updating asset {:?}@{} (type {}, {} bytes loaded)",
-            load_handle,
-            version,
-            std::any::type_name::<A>(),
-            data.len()
-        );
+        let load_handles = self.load_handles.clone();
+        let refop_sender = (*self.assets.refop_sender).clone();
+        let deserialized = self.assets.deserialized.0.clone();
+        let deserialized_updates = self.deserialized_updates.clone();
+        let schema = self.assets.schema;
+
+        self.task_pool
+            .spawn(async move {
+                use bincode::Options;
+
+                // To enable automatic serde of Handle, we need to set up a SerdeContext with a RefOp sender.
+                // Handles are resolved through a copy of the loader's load handles.
+                let asset = futures_executor::block_on(distill_loader::handle::SerdeContext::with(
+                    &load_handles,
+                    refop_sender,
+                    async {
+                        let (data_version, asset_data) = schema::split_version(&data);
+                        if data_version == schema.version() {
+                            schema::bincode_options()
+                                .deserialize_seed::<D>(seed, asset_data)
+                                .map_err(SchemaError::Deserialize)
+                        } else {
+                            schema.migrate(data_version, asset_data)
+                        }
+                    },
+                ));
+                let asset = match asset {
+                    Ok(asset) => asset,
+                    Err(e) => {
+                        let _ = deserialized_updates.send(Err((load_op, e)));
+                        return;
+                    }
+                };
+
+                bevy_log::trace!(
+                    "updating asset {:?}@{} (type {}, {} bytes loaded)",
+                    load_handle,
+                    version,
+                    std::any::type_name::<A>(),
+                    data.len()
+                );
+
+                // sent before the update, so the asset is there once the loader commits it
+                let _ = deserialized.send(DeserializedAsset {
+                    load_handle,
+                    state: AssetState { version, asset },
+                });
+                let _ = deserialized_updates.send(Ok(DeserializedUpdate {
+                    load_handle,
+                    load_op,
+                    version,
+                }));
+            })
+            .detach();
 
         Ok(())
     }
@@ -229,31 +318,15 @@ where
             version,
             std::any::type_name::<A>(),
         );
-        let handle = WeakHandle::new(load_handle);
-        self.assets
-            .events
-            .send(AssetEvent::Modified { handle, version });
-
-        // The commit step is done after an asset load has completed.
-        // It exists to avoid frames where an asset that was loaded is unloaded, which
-        // could happen when hot reloading. To support this case, you must support having multiple
-        // versions of an asset loaded at the same time.
-        let asset_state = self
-            .assets
-            .uncommitted
-            .remove(&load_handle)
-            .expect("asset not present when committing");
-        self.assets.assets.insert(load_handle, asset_state);
+        self.assets.commit(load_handle, version);
     }
 
     fn free(&mut self, asset_type: &AssetTypeId, load_handle: LoadHandle, version: u32) {
         debug_assert_eq!(*A::TYPE_UUID.as_bytes(), asset_type.0);
 
-        if let Some(asset) = self.assets.uncommitted.get(&load_handle) {
-            if asset.version == version {
-                self.assets.uncommitted.remove(&load_handle);
-            }
-        }
+        self.assets.receive_deserialized();
+
+        self.assets.uncommitted.remove(&(load_handle, version));
         if let Some(asset) = self.assets.assets.get(&load_handle) {
             if asset.version == version {
                 self.assets.assets.remove(&load_handle);
@@ -273,33 +346,237 @@ where
 type AssetStorageProvider =
     Box<dyn (Fn(&mut World, &mut dyn FnMut(&mut dyn AssetStorage))) + Send + Sync + 'static>;
 
+type AssetRemover = fn(&mut World, LoadHandle);
+
 #[derive(Default)]
-pub struct AssetResources(HashMap<AssetTypeId, AssetStorageProvider>);
+struct LoadHandles {
+    by_uuid: HashMap<AssetUuid, LoadHandle>,
+    by_handle: HashMap<LoadHandle, AssetUuid>,
+}
+
+/// A [`LoaderInfoProvider`] for deserializing handles off the main thread.
+///
+/// The loader can't be shared with tasks, so the load handles of the assets are copied before they are spawned.
+/// The handles in an asset are its load dependencies, which the loader loads before the asset itself,
+/// so they already went through an earlier batch, or are in the same one.
+#[derive(Clone, Default)]
+struct LoadHandleTable(Arc<RwLock<LoadHandles>>);
+
+impl LoadHandleTable {
+    /// Adds the loads of a batch of updates and removes the freed loads the loader doesn't know anymore.
+    fn update(&self, loader: &Loader, batch: &[QueuedUpdate], freed: &[LoadHandle]) {
+        if batch.is_empty() && freed.is_empty() {
+            return;
+        }
+        let mut load_handles = self.0.write().unwrap();
+        for &load_handle in freed {
+            if loader.get_load_info(load_handle).is_some() {
+                continue;
+            }
+            if let Some(uuid) = load_handles.by_handle.remove(&load_handle) {
+                if load_handles.by_uuid.get(&uuid) == Some(&load_handle) {
+                    load_handles.by_uuid.remove(&uuid);
+                }
+            }
+        }
+        for update in batch {
+            if let Some(info) = loader.get_load_info(update.load_handle) {
+                load_handles
+                    .by_uuid
+                    .insert(info.asset_id, update.load_handle);
+                load_handles
+                    .by_handle
+                    .insert(update.load_handle, info.asset_id);
+            }
+        }
+    }
+}
+
+impl LoaderInfoProvider for LoadHandleTable {
+    fn get_load_handle(&self, id: &AssetRef) -> Option<LoadHandle> {
+        match id {
+            AssetRef::Uuid(uuid) => self.0.read().unwrap().by_uuid.get(uuid).copied(),
+            // like the loader, which only knows paths through indirect handles
+            AssetRef::Path(_) => None,
+        }
+    }
+
+    fn get_asset_id(&self, load: LoadHandle) -> Option<AssetUuid> {
+        self.0.read().unwrap().by_handle.get(&load).copied()
+    }
+}
+
+pub struct AssetResources {
+    storages: HashMap<AssetTypeId, AssetStorageProvider>,
+    removers: Vec<AssetRemover>,
+    load_handles: LoadHandleTable,
+    deserialized_updates: (Sender<DeserializeResult>, Receiver<DeserializeResult>),
+}
+
+impl Default for AssetResources {
+    fn default() -> Self {
+        AssetResources {
+            storages: HashMap::default(),
+            removers: Vec::new(),
+            load_handles: LoadHandleTable::default(),
+            deserialized_updates: unbounded(),
+        }
+    }
+}
+
 impl AssetResources {
     pub fn add<A, D>(&mut self)
     where
         A: Asset,
-        D: FromWorld + for<'de> DeserializeSeed<'de, Value = A> + Clone,
+        D: FromWorld + for<'de> DeserializeSeed<'de, Value = A> + Clone + Send + 'static,
     {
         let asset_type = AssetTypeId(*A::TYPE_UUID.as_bytes());
-        self.0.insert(
+        let load_handles = self.load_handles.clone();
+        let deserialized_updates = self.deserialized_updates.0.clone();
+        self.storages.insert(
             asset_type,
-            Box::new(|world, callback| {
+            Box::new(move |world, callback| {
                 let seed = D::from_world(world);
+                let task_pool = world
+                    .get_resource::<AsyncComputeTaskPool>()
+                    .expect("the `AssetPlugin` inserts an `AsyncComputeTaskPool`")
+                    .clone();
                 let assets = world.get_resource_mut::<Assets<A>>().unwrap().into_inner();
-                let mut storage = AssetStorageData { seed, assets };
+                let mut storage = AssetStorageData {
+                    seed,
+                    assets,
+                    task_pool,
+                    load_handles: load_handles.clone(),
+                    deserialized_updates: deserialized_updates.clone(),
+                };
 
                 callback(&mut storage);
             }),
         );
+        self.removers.push(|world, load_handle| {
+            world
+                .get_resource_mut::<Assets<A>>()
+                .unwrap()
+                .remove(&WeakHandle::<A>::new(load_handle));
+        });
     }
+
+    /// Removes the asset from whichever [`Assets`] collection contains it.
+    pub(crate) fn remove(&self, world: &mut World, load_handle: LoadHandle) {
+        for remover in &self.removers {
+            remover(world, load_handle);
+        }
+    }
+}
+
+/// Loaded asset data waiting to be deserialized.
+struct QueuedUpdate {
+    asset_type: AssetTypeId,
+    data: Vec<u8>,
+    load_handle: LoadHandle,
+    load_op: AssetLoadOp,
+    version: u32,
+}
+
+/// An asset deserialized by a task, whose load is completed within the [`AssetLoadBudget`].
+struct DeserializedUpdate {
+    load_handle: LoadHandle,
+    load_op: AssetLoadOp,
+    version: u32,
+}
+
+/// Sent by the deserialization tasks. Failed loads are reported right away.
+type DeserializeResult = Result<DeserializedUpdate, (AssetLoadOp, SchemaError)>;
+
+#[derive(Default)]
+pub(crate) struct AssetUpdateQueue {
+    queued: Vec<QueuedUpdate>,
+    deserialized: Vec<DeserializedUpdate>,
+    /// Removed from the [`LoadHandleTable`] with the next batch, once the loader doesn't know them anymore.
+    freed: Vec<LoadHandle>,
 }
 
 pub(crate) struct WorldAssetStorage<'w> {
     pub world: &'w mut World,
     pub asset_resources: &'w AssetResources,
+    pub queue: &'w mut AssetUpdateQueue,
 }
 impl<'w> WorldAssetStorage<'w> {
+    /// Starts deserializing the queued assets and completes the loads of deserialized assets
+    /// until the budget is used up, highest priority first.
+    ///
+    /// A time budget counts the main thread time since `started`, when the frame's asset handling began.
+    pub fn update_queued(
+        &mut self,
+        loader: &Loader,
+        budget: AssetLoadBudget,
+        started: Instant,
+        priority: impl Fn(LoadHandle) -> i32,
+    ) {
+        // stable, so assets with the same priority are handled in the order they were loaded
+        let mut queued = std::mem::take(&mut self.queue.queued);
+        queued.sort_by_key(|update| std::cmp::Reverse(priority(update.load_handle)));
+        let freed = std::mem::take(&mut self.queue.freed);
+        self.asset_resources
+            .load_handles
+            .update(loader, &queued, &freed);
+        for update in queued {
+            // only fails if the storage couldn't report the failure through the load op itself
+            let result = self.with(&update.asset_type, |storage| {
+                storage.update_asset(
+                    loader,
+                    &update.asset_type,
+                    update.data,
+                    update.load_handle,
+                    update.load_op,
+                    update.version,
+                )
+            });
+            if let Err(e) = result {
+                bevy_log::error!("failed to update asset {:?}: {}", update.load_handle, e);
+            }
+        }
+
+        for result in self.asset_resources.deserialized_updates.1.try_iter() {
+            match result {
+                Ok(update) => self.queue.deserialized.push(update),
+                Err((load_op, e)) => load_op.error(e),
+            }
+        }
+        if self.queue.deserialized.is_empty() {
+            return;
+        }
+        self.queue
+            .deserialized
+            .sort_by_key(|update| std::cmp::Reverse(priority(update.load_handle)));
+
+        let mut handled = 0;
+        let mut deserialized = std::mem::take(&mut self.queue.deserialized).into_iter();
+        for update in &mut deserialized {
+            // the loader commits the asset the next time it is processed
+            update.load_op.complete();
+            handled += 1;
+
+            let exceeded = match budget {
+                AssetLoadBudget::Unlimited => false,
+                AssetLoadBudget::Time(time) => started.elapsed() >= time,
+                AssetLoadBudget::Assets(assets) => handled >= assets,
+            };
+            if exceeded {
+                break;
+            }
+        }
+        self.queue.deserialized.extend(deserialized);
+
+        if !self.queue.deserialized.is_empty() {
+            bevy_log::trace!(
+                "{} assets loaded, {} deferred to the next frame",
+                handled,
+                self.queue.deserialized.len()
+            );
+        }
+    }
+
     fn with<R>(
         &mut self,
         asset_type: &AssetTypeId,
@@ -307,7 +584,7 @@ impl<'w> WorldAssetStorage<'w> {
     ) -> R {
         let func = self
             .asset_resources
-            .0
+            .storages
             .get(asset_type)
             .unwrap_or_else(|| panic!("asset not registered: {}", asset_type));
 
@@ -325,23 +602,23 @@ impl<'w> WorldAssetStorage<'w> {
 impl AssetStorage for WorldAssetStorage<'_> {
     fn update_asset(
         &mut self,
-        loader_info: &dyn LoaderInfoProvider,
+        _loader_info: &dyn LoaderInfoProvider,
         asset_type_id: &AssetTypeId,
         data: Vec<u8>,
         load_handle: LoadHandle,
         load_op: AssetLoadOp,
         version: u32,
     ) -> Result<(), Box<dyn Error + Send + 'static>> {
-        self.with(asset_type_id, |storage| {
-            storage.update_asset(
-                loader_info,
-                asset_type_id,
-                data,
-                load_handle,
-                load_op,
-                version,
-            )
-        })
+        // the load op is completed once the asset is deserialized and within the budget
+        // in `update_queued`, so the asset keeps its loading status until then
+        self.queue.queued.push(QueuedUpdate {
+            asset_type: *asset_type_id,
+            data,
+            load_handle,
+            load_op,
+            version,
+        });
+        Ok(())
     }
 
     fn commit_asset_version(
@@ -356,8 +633,64 @@ impl AssetStorage for WorldAssetStorage<'_> {
     }
 
     fn free(&mut self, asset_type_id: &AssetTypeId, load_handle: LoadHandle, version: u32) {
+        self.queue
+            .queued
+            .retain(|update| !(update.load_handle == load_handle && update.version == version));
+        self.queue
+            .deserialized
+            .retain(|update| !(update.load_handle == load_handle && update.version == version));
+        self.queue.freed.push(load_handle);
         self.with(asset_type_id, |storage| {
             storage.free(asset_type_id, load_handle, version)
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plugin::in_memory_app;
+    use crate::prelude::*;
+
+    #[derive(Serialize, Deserialize, TypeUuid)]
+    #[uuid = "0f5d8e2a-4b6c-4d1e-8a3f-9c7b2e1d0a54"]
+    struct TestAsset(u32);
+
+    fn deserialized(assets: &Assets<TestAsset>, load_handle: LoadHandle, version: u32) {
+        let state = AssetState {
+            version,
+            asset: TestAsset(version),
+        };
+        assets
+            .deserialized
+            .0
+            .send(DeserializedAsset { load_handle, state })
+            .unwrap();
+    }
+
+    #[test]
+    fn stale_versions_are_dropped() {
+        let mut app = in_memory_app(MemoryAssetSource::new());
+        app.add_asset::<TestAsset>();
+        let mut assets = app.world.get_resource_mut::<Assets<TestAsset>>().unwrap();
+        let load_handle = LoadHandle(1);
+        let handle = WeakHandle::<TestAsset>::new(load_handle);
+
+        // a reload finished before the previous version was committed
+        deserialized(&assets, load_handle, 1);
+        deserialized(&assets, load_handle, 2);
+        assets.commit(load_handle, 2);
+        assert_eq!(assets.get(&handle).unwrap().0, 2);
+        assert!(assets.uncommitted.is_empty());
+
+        // the commit of the superseded version doesn't replace the newer one
+        assets.commit(load_handle, 1);
+        assert_eq!(assets.get(&handle).unwrap().0, 2);
+
+        // neither does a superseded version that finished deserializing late
+        deserialized(&assets, load_handle, 1);
+        assets.receive_deserialized();
+        assert!(assets.uncommitted.is_empty());
+        assert_eq!(assets.get_version(&handle), Some(2));
+    }
+}
@@ -1,5 +1,9 @@
+use std::cell::RefCell;
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+
 use crate::prelude::*;
-use distill_core::AssetUuid;
+use distill_core::{AssetRef, AssetUuid};
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
@@ -8,8 +12,343 @@ use uuid::Uuid;
 pub struct AssetUuidImporterState(Option<AssetUuid>);
 impl AssetUuidImporterState {
     pub fn id(&mut self) -> AssetUuid {
-        *self
-            .0
-            .get_or_insert_with(|| AssetUuid(*Uuid::new_v4().as_bytes()))
+        *self.0.get_or_insert_with(|| new_asset_uuid(None))
+    }
+}
+
+thread_local! {
+    static LOAD_DEPS: RefCell<Option<Vec<AssetRef>>> = RefCell::new(None);
+}
+
+/// Runs `f` and returns the assets referenced by every [`Handle`](crate::Handle) and
+/// [`HandleUntyped`](crate::HandleUntyped) deserialized from a human-readable format inside of it,
+/// without duplicates.
+///
+/// Importers use these as the `load_deps` of the imported asset, so the loader requests
+/// the dependencies together with the asset instead of after deserializing it.
+/// Handles deserialized by a nested call only end up in the result of that call.
+pub fn collect_load_deps<R>(f: impl FnOnce() -> R) -> (R, Vec<AssetRef>) {
+    let outer = LOAD_DEPS.with(|load_deps| load_deps.replace(Some(Vec::new())));
+    let result = f();
+    let load_deps = LOAD_DEPS.with(|load_deps| load_deps.replace(outer));
+    (result, load_deps.unwrap_or_default())
+}
+
+pub(crate) fn collecting_load_deps() -> bool {
+    LOAD_DEPS.with(|load_deps| load_deps.borrow().is_some())
+}
+
+/// Records a handle deserialized from `asset_ref`, which is a UUID or a path.
+pub(crate) fn record_load_dep(asset_ref: &str) {
+    let asset_ref = match Uuid::parse_str(asset_ref) {
+        Ok(uuid) => AssetRef::Uuid(AssetUuid(*uuid.as_bytes())),
+        Err(_) => AssetRef::Path(PathBuf::from(asset_ref)),
+    };
+    LOAD_DEPS.with(|load_deps| {
+        if let Some(load_deps) = &mut *load_deps.borrow_mut() {
+            if !load_deps.contains(&asset_ref) {
+                load_deps.push(asset_ref);
+            }
+        }
+    });
+}
+
+/// Namespace of the UUIDs derived by [`deterministic_asset_uuid`].
+const ASSET_PATH_NAMESPACE: Uuid = Uuid::from_bytes([
+    0xcf, 0xe6, 0x34, 0x29, 0x46, 0xc8, 0x40, 0x38, 0xa7, 0x02, 0xdb, 0x4e, 0x19, 0x2e, 0xf0, 0xd0,
+]);
+
+/// The UUID of the asset at `path`, relative to the asset dir, with the sub-asset `label`,
+/// as assigned by importers running in [`with_deterministic_uuids`].
+///
+/// This is a name-based (version 5) UUID of `path` with `/` separators, followed by `#label`.
+pub fn deterministic_asset_uuid(path: &Path, label: Option<&str>) -> AssetUuid {
+    let mut name = path.to_string_lossy().replace('\\', "/");
+    if let Some(label) = label {
+        name.push('#');
+        name.push_str(label);
+    }
+    AssetUuid(*Uuid::new_v5(&ASSET_PATH_NAMESPACE, name.as_bytes()).as_bytes())
+}
+
+thread_local! {
+    static IMPORT_PATH: RefCell<Option<PathBuf>> = RefCell::new(None);
+}
+
+/// Runs `f` with importer states assigning [`deterministic_asset_uuid`]s for `path` instead of random UUIDs,
+/// so the same sources get the same UUIDs on every machine, even without `.meta` files.
+///
+/// UUIDs which are already in the importer state, e.g. from a `.meta` file, are kept.
+/// The asset daemon doesn't tell importers which file they import, so it can't use this
+/// and writes random UUIDs to the `.meta` files instead.
+pub fn with_deterministic_uuids<R>(path: &Path, f: impl FnOnce() -> R) -> R {
+    let previous = IMPORT_PATH.with(|import_path| import_path.replace(Some(path.to_path_buf())));
+    let result = f();
+    IMPORT_PATH.with(|import_path| *import_path.borrow_mut() = previous);
+    result
+}
+
+fn new_asset_uuid(label: Option<&str>) -> AssetUuid {
+    IMPORT_PATH.with(|import_path| match &*import_path.borrow() {
+        Some(path) => deterministic_asset_uuid(path, label),
+        None => AssetUuid(*Uuid::new_v4().as_bytes()),
+    })
+}
+
+/// Importer state for importers which produce labelled sub-assets next to their main asset.
+///
+/// Also reads the state written for [`AssetUuidImporterState`], so an importer can switch
+/// to this state without losing the UUIDs in existing `.meta` files.
+#[derive(TypeUuid, Serialize, Deserialize, Default)]
+#[serde(from = "LabeledUuidStateRepr")]
+#[uuid = "2023f0f4-9ff9-4c08-9504-2ed199bcfb3c"]
+pub struct LabeledUuidImporterState {
+    id: Option<AssetUuid>,
+    labels: BTreeMap<String, AssetUuid>,
+    #[serde(skip)]
+    referenced: BTreeSet<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum LabeledUuidStateRepr {
+    Labeled {
+        id: Option<AssetUuid>,
+        #[serde(default)]
+        labels: BTreeMap<String, AssetUuid>,
+    },
+    Single((Option<AssetUuid>,)),
+}
+
+impl From<LabeledUuidStateRepr> for LabeledUuidImporterState {
+    fn from(repr: LabeledUuidStateRepr) -> Self {
+        let (id, labels) = match repr {
+            LabeledUuidStateRepr::Labeled { id, labels } => (id, labels),
+            LabeledUuidStateRepr::Single((id,)) => (id, BTreeMap::new()),
+        };
+        LabeledUuidImporterState {
+            id,
+            labels,
+            referenced: BTreeSet::new(),
+        }
+    }
+}
+
+impl LabeledUuidImporterState {
+    /// The UUID of the main asset.
+    pub fn id(&mut self) -> AssetUuid {
+        *self.id.get_or_insert_with(|| new_asset_uuid(None))
+    }
+
+    /// The UUID of the sub-asset with `label`, which stays the same across imports.
+    pub fn label_id(&mut self, label: &str) -> AssetUuid {
+        if let Some(id) = self.labels.get(label) {
+            return *id;
+        }
+        let id = new_asset_uuid(Some(label));
+        self.labels.insert(label.to_string(), id);
+        id
+    }
+
+    /// The labels which `#label` handles pointed at during the last [`with_sibling_labels`].
+    pub fn referenced_labels(&self) -> impl Iterator<Item = &str> {
+        self.referenced.iter().map(String::as_str)
+    }
+
+    /// Forgets the UUIDs of labels for which `keep` returns false, e.g. because the sub-asset was removed from the source.
+    pub fn retain_labels(&mut self, mut keep: impl FnMut(&str) -> bool) {
+        self.labels.retain(|label, _| keep(label));
+    }
+}
+
+thread_local! {
+    static SIBLING_LABELS: RefCell<Option<LabeledUuidImporterState>> = RefCell::new(None);
+}
+
+/// Runs `f` with [`Handle`](crate::Handle)s written as `"#Label"` resolving to the sub-asset
+/// with that label in `state`, so that assets can point at other assets of the same source file.
+///
+/// Importers wrap the deserialization of their source in this. Labels without a UUID yet get a new one,
+/// so the importer should check [`referenced_labels`](LabeledUuidImporterState::referenced_labels)
+/// against the sub-assets it actually produced.
+pub fn with_sibling_labels<R>(state: &mut LabeledUuidImporterState, f: impl FnOnce() -> R) -> R {
+    state.referenced.clear();
+    SIBLING_LABELS.with(|labels| *labels.borrow_mut() = Some(std::mem::take(state)));
+    let result = f();
+    *state = SIBLING_LABELS
+        .with(|labels| labels.borrow_mut().take())
+        .unwrap();
+    result
+}
+
+pub(crate) fn resolving_sibling_labels() -> bool {
+    SIBLING_LABELS.with(|labels| labels.borrow().is_some())
+}
+
+pub(crate) fn reference_sibling_label(label: &str) -> Option<AssetUuid> {
+    SIBLING_LABELS.with(|labels| {
+        labels.borrow_mut().as_mut().map(|state| {
+            state.referenced.insert(label.to_string());
+            state.label_id(label)
+        })
+    })
+}
+
+/// A 64 bit FNV-1a hash, for values which are written to disk.
+///
+/// Unlike [`DefaultHasher`](std::collections::hash_map::DefaultHasher), the result is the same
+/// in every build and on every platform, so it doesn't go through [`Hash`](std::hash::Hash),
+/// whose implementations depend on the platform's endianness and pointer width.
+pub(crate) struct StableHasher(u64);
+
+impl StableHasher {
+    pub fn new() -> Self {
+        StableHasher(0xcbf29ce484222325)
+    }
+
+    pub fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 ^= byte as u64;
+            self.0 = self.0.wrapping_mul(0x100000001b3);
+        }
+    }
+
+    pub fn write_u32(&mut self, value: u32) {
+        self.write(&value.to_le_bytes());
+    }
+
+    pub fn finish(&self) -> u64 {
+        self.0
+    }
+
+    /// The hash folded to 32 bits, for importer versions.
+    pub fn finish_u32(&self) -> u32 {
+        (self.0 >> 32) as u32 ^ self.0 as u32
+    }
+}
+
+/// Derives the UUID of a generic importer from the UUID of the asset type it imports,
+/// so that for example `RonImporter<Material>` and `RonImporter<Texture>` are different importers.
+///
+/// This is a name-based (version 5) UUID with the importer's own UUID as namespace.
+pub const fn importer_uuid(namespace: &[u8; 16], asset_type: &[u8; 16]) -> [u8; 16] {
+    // SHA-1 of the 32 byte message `namespace || asset_type`, which fits into a single block
+    let mut block = [0u8; 64];
+    let mut i = 0;
+    while i < 16 {
+        block[i] = namespace[i];
+        block[16 + i] = asset_type[i];
+        i += 1;
+    }
+    block[32] = 0x80;
+    // message length in bits, big endian
+    block[62] = 0x01;
+
+    let mut w = [0u32; 80];
+    let mut t = 0;
+    while t < 16 {
+        w[t] = u32::from_be_bytes([
+            block[4 * t],
+            block[4 * t + 1],
+            block[4 * t + 2],
+            block[4 * t + 3],
+        ]);
+        t += 1;
+    }
+    while t < 80 {
+        w[t] = (w[t - 3] ^ w[t - 8] ^ w[t - 14] ^ w[t - 16]).rotate_left(1);
+        t += 1;
+    }
+
+    let h = [
+        0x67452301u32,
+        0xEFCDAB89,
+        0x98BADCFE,
+        0x10325476,
+        0xC3D2E1F0,
+    ];
+    let (mut a, mut b, mut c, mut d, mut e) = (h[0], h[1], h[2], h[3], h[4]);
+    let mut t = 0;
+    while t < 80 {
+        let (f, k) = if t < 20 {
+            ((b & c) | (!b & d), 0x5A827999)
+        } else if t < 40 {
+            (b ^ c ^ d, 0x6ED9EBA1)
+        } else if t < 60 {
+            ((b & c) | (b & d) | (c & d), 0x8F1BBCDC)
+        } else {
+            (b ^ c ^ d, 0xCA62C1D6)
+        };
+        let temp = a
+            .rotate_left(5)
+            .wrapping_add(f)
+            .wrapping_add(e)
+            .wrapping_add(k)
+            .wrapping_add(w[t]);
+        e = d;
+        d = c;
+        c = b.rotate_left(30);
+        b = a;
+        a = temp;
+        t += 1;
+    }
+    let digest = [
+        h[0].wrapping_add(a),
+        h[1].wrapping_add(b),
+        h[2].wrapping_add(c),
+        h[3].wrapping_add(d),
+    ];
+
+    let mut uuid = [0u8; 16];
+    let mut i = 0;
+    while i < 4 {
+        let bytes = digest[i].to_be_bytes();
+        uuid[4 * i] = bytes[0];
+        uuid[4 * i + 1] = bytes[1];
+        uuid[4 * i + 2] = bytes[2];
+        uuid[4 * i + 3] = bytes[3];
+        i += 1;
+    }
+    uuid[6] = (uuid[6] & 0x0f) | 0x50;
+    uuid[8] = (uuid[8] & 0x3f) | 0x80;
+    uuid
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stable_hasher_is_fnv1a() {
+        let hash = |bytes: &[u8]| {
+            let mut hasher = StableHasher::new();
+            hasher.write(bytes);
+            hasher.finish()
+        };
+        assert_eq!(hash(b""), 0xcbf29ce484222325);
+        assert_eq!(hash(b"a"), 0xaf63dc4c8601ec8c);
+        assert_eq!(hash(b"foobar"), 0x85944171f73967e8);
+    }
+
+    #[test]
+    fn importer_uuid_is_uuid_v5() {
+        let namespaces = [
+            Uuid::nil(),
+            ASSET_PATH_NAMESPACE,
+            Uuid::parse_str("aa4f1952-30ac-475a-87e1-2b060d775b32").unwrap(),
+        ];
+        let asset_types = [
+            Uuid::nil(),
+            Uuid::parse_str("3b8c2b3e-6f0a-4a55-9a3e-0c5c8a9b1f27").unwrap(),
+            Uuid::from_bytes([0xff; 16]),
+        ];
+        for namespace in &namespaces {
+            for asset_type in &asset_types {
+                assert_eq!(
+                    Uuid::from_bytes(importer_uuid(namespace.as_bytes(), asset_type.as_bytes())),
+                    Uuid::new_v5(namespace, asset_type.as_bytes()),
+                );
+            }
+        }
     }
 }
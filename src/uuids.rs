@@ -0,0 +1,115 @@
+//! Checking asset UUIDs against the ones derived from asset paths.
+//!
+//! Sources imported with deterministic UUIDs, see [`deterministic_asset_uuid`], get the same UUIDs
+//! on every machine without `.meta` files. Before relying on that, [`check`] finds the existing
+//! `.meta` files whose UUIDs would change.
+//!
+//! Only [`DirectorySettings::with_deterministic_uuids`](crate::DirectorySettings::with_deterministic_uuids)
+//! and packfile builds derive UUIDs from paths. An asset daemon writes random UUIDs to the `.meta` files of new sources.
+
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use bevy_app::App;
+use distill_core::AssetUuid;
+use distill_importer::BoxedImporter;
+
+use crate::io::import::{self, ImportError};
+use crate::plugin::{registered_importers, registered_schemas};
+use crate::util::deterministic_asset_uuid;
+
+/// An asset whose UUID in the `.meta` file isn't the one derived from its path.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UuidMismatch {
+    /// The source path, relative to the asset dir.
+    pub path: PathBuf,
+    /// The label of the sub-asset, `None` for the main asset of the source.
+    pub label: Option<String>,
+    pub meta: AssetUuid,
+    pub derived: AssetUuid,
+}
+
+impl fmt::Display for UuidMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.path.display())?;
+        if let Some(label) = &self.label {
+            write!(f, "#{}", label)?;
+        }
+        write!(
+            f,
+            ": {} in .meta file, {} derived from path",
+            uuid::Uuid::from_bytes(self.meta.0),
+            uuid::Uuid::from_bytes(self.derived.0)
+        )
+    }
+}
+
+/// Checks the sources in `asset_dirs` with the importers registered on `app` through [`AddAsset`](crate::AddAsset).
+pub fn check_from_app(
+    app: &App,
+    asset_dirs: &[impl AsRef<Path>],
+) -> Result<Vec<UuidMismatch>, ImportError> {
+    let importers = registered_importers(&app.world);
+    let importers = import::boxed_importers(
+        &importers.read().unwrap(),
+        &registered_schemas(&app.world),
+        None,
+    );
+    check(asset_dirs, &importers)
+}
+
+/// Imports every source in `asset_dirs` that has a `.meta` file and returns the assets
+/// whose UUIDs differ from the [`deterministic_asset_uuid`] of their path and label.
+///
+/// The first asset without a label is the main asset of a source. Further unlabelled assets,
+/// which some importers produce with UUIDs of their own, aren't checked.
+pub fn check(
+    asset_dirs: &[impl AsRef<Path>],
+    importers: &[(&'static [&'static str], Box<dyn BoxedImporter>)],
+) -> Result<Vec<UuidMismatch>, ImportError> {
+    let mut mismatches = Vec::new();
+
+    for dir in asset_dirs {
+        let dir = dir.as_ref();
+        let mut files = Vec::new();
+        import::collect_sources(dir, &mut files)
+            .map_err(|e| ImportError::Io(dir.to_path_buf(), e))?;
+        files.sort();
+
+        for path in files {
+            let importer = match import::find_importer(importers, &path) {
+                Some(i) => &importers[i].1,
+                None => continue,
+            };
+            if !import::meta_path(&path).exists() {
+                continue;
+            }
+            let relative = path.strip_prefix(dir).unwrap_or(&path);
+
+            let mut checked_main = false;
+            let source = import::import_source(&**importer, &path, None, None)?;
+            for asset in source.assets() {
+                let label = asset
+                    .search_tags
+                    .iter()
+                    .find(|(tag, _)| tag == "name")
+                    .and_then(|(_, label)| label.clone());
+                if label.is_none() && std::mem::replace(&mut checked_main, true) {
+                    continue;
+                }
+
+                let derived = deterministic_asset_uuid(relative, label.as_deref());
+                if asset.id != derived {
+                    mismatches.push(UuidMismatch {
+                        path: relative.to_path_buf(),
+                        label,
+                        meta: asset.id,
+                        derived,
+                    });
+                }
+            }
+        }
+    }
+
+    Ok(mismatches)
+}